@@ -4,3 +4,4 @@ rustup toolchain install stable-$VER
 rustup default stable-$VER
 rustup target add wasm32-unknown-unknown
 cargo build -p nft-contract --target wasm32-unknown-unknown --release
+cargo build -p fraction-share --target wasm32-unknown-unknown --release
@@ -2,4 +2,8 @@
 set -e
 cd "`dirname $0`"
 RUSTFLAGS='-C link-arg=-s' cargo build --all --target wasm32-unknown-unknown --release
-cp target/wasm32-unknown-unknown/release/*.wasm ./out/main.wasm
+cp target/wasm32-unknown-unknown/release/paras_nft_contract.wasm ./out/main.wasm
+cp target/wasm32-unknown-unknown/release/fraction_share.wasm ./out/fraction_share.wasm
+cp target/wasm32-unknown-unknown/release/token_gate.wasm ./out/token_gate.wasm
+RUSTFLAGS='-C link-arg=-s' cargo build -p paras-nft-contract --features legacy-token-metadata --target wasm32-unknown-unknown --release
+cp target/wasm32-unknown-unknown/release/paras_nft_contract.wasm ./out/main_legacy.wasm
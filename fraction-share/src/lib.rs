@@ -0,0 +1,103 @@
+use near_contract_standards::fungible_token::metadata::{
+    FungibleTokenMetadata, FungibleTokenMetadataProvider,
+};
+use near_contract_standards::fungible_token::FungibleToken;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LazyOption;
+use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::{env, log, near_bindgen, AccountId, Balance, BorshStorageKey, PanicOnDefault, PromiseOrValue};
+
+near_sdk::setup_alloc!();
+
+/// NEP-141 shares of a token fractionalized by the NFT contract that deploys this one.
+/// The whole supply is minted once to the vault owner.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    token: FungibleToken,
+    metadata: LazyOption<FungibleTokenMetadata>,
+}
+
+#[derive(BorshSerialize, BorshStorageKey)]
+enum StorageKey {
+    FungibleToken,
+    Metadata,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Called by the NFT contract right after deploying, which is registered so holders can
+    /// send shares back to it with `ft_transfer_call`
+    #[init]
+    pub fn new(owner_id: ValidAccountId, total_supply: U128, metadata: FungibleTokenMetadata) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        metadata.assert_valid();
+        let mut this = Self {
+            token: FungibleToken::new(StorageKey::FungibleToken),
+            metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
+        };
+        this.token.internal_register_account(&env::predecessor_account_id());
+        if owner_id.as_ref() != &env::predecessor_account_id() {
+            this.token.internal_register_account(owner_id.as_ref());
+        }
+        this.token.internal_deposit(owner_id.as_ref(), total_supply.into());
+        this
+    }
+
+    fn on_account_closed(&mut self, account_id: AccountId, balance: Balance) {
+        log!("Closed @{} with {}", account_id, balance);
+    }
+
+    fn on_tokens_burned(&mut self, account_id: AccountId, amount: Balance) {
+        log!("Account @{} burned {}", account_id, amount);
+    }
+}
+
+near_contract_standards::impl_fungible_token_core!(Contract, token, on_tokens_burned);
+near_contract_standards::impl_fungible_token_storage!(Contract, token, on_account_closed);
+
+#[near_bindgen]
+impl FungibleTokenMetadataProvider for Contract {
+    fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.metadata.get().unwrap()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_contract_standards::fungible_token::core::FungibleTokenCore;
+    use near_contract_standards::fungible_token::metadata::FT_METADATA_SPEC;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_new_mints_supply_to_owner() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .current_account_id(accounts(1))
+            .predecessor_account_id(accounts(0))
+            .build()
+        );
+
+        let contract = Contract::new(
+            accounts(2),
+            U128::from(1_000),
+            FungibleTokenMetadata {
+                spec: FT_METADATA_SPEC.to_string(),
+                name: "1:1 shares".to_string(),
+                symbol: "SHARE".to_string(),
+                icon: None,
+                reference: None,
+                reference_hash: None,
+                decimals: 0,
+            },
+        );
+
+        assert_eq!(contract.ft_total_supply(), U128::from(1_000));
+        assert_eq!(contract.ft_balance_of(accounts(2)), U128::from(1_000));
+        assert_eq!(contract.ft_balance_of(accounts(0)), U128::from(0));
+        assert_eq!(contract.ft_metadata().symbol, "SHARE");
+    }
+}
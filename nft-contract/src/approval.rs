@@ -0,0 +1,793 @@
+use crate::*;
+use near_contract_standards::non_fungible_token::approval::NonFungibleTokenApproval;
+
+const MAX_ACCOUNTS_PER_APPROVE: usize = 10;
+
+#[ext_contract(ext_operator_receiver)]
+pub trait OperatorReceiver {
+    fn nft_on_approval_for_all(
+        &mut self,
+        owner_id: AccountId,
+        approved: bool,
+        token_series_id: Option<TokenSeriesId>,
+        msg: String,
+    );
+}
+
+/// Limits on a per-token approval, kept in sync with `approvals_by_id`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApprovalTerms {
+    expires_at: Option<U64>,
+    min_price: Option<U128>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApprovalJson {
+    approval_id: u64,
+    expires_at: Option<U64>,
+    /// Nanoseconds until `expires_at`
+    remaining: Option<U64>,
+    min_price: Option<U128>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OperatorJson {
+    operator_id: AccountId,
+    token_series_id: Option<TokenSeriesId>,
+}
+
+#[near_bindgen]
+impl NonFungibleTokenApproval for Contract {
+    #[payable]
+    fn nft_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: ValidAccountId,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        self.nft_approve_many(token_id, vec![account_id], msg, None, None)
+    }
+
+    /// Refunds the released storage to the token owner
+    #[payable]
+    fn nft_revoke(&mut self, token_id: TokenId, account_id: ValidAccountId) {
+        assert_one_yocto();
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = self.assert_token_owner(&token_id);
+
+        let approvals_by_id = self.tokens.approvals_by_id.as_mut().unwrap();
+        let mut approved_account_ids = approvals_by_id.get(&token_id).unwrap_or_default();
+        if approved_account_ids.remove(account_id.as_ref()).is_none() {
+            return;
+        }
+        if approved_account_ids.is_empty() {
+            approvals_by_id.remove(&token_id);
+        } else {
+            approvals_by_id.insert(&token_id, &approved_account_ids);
+        }
+        self.internal_remove_approval_terms(&token_id, &[account_id.to_string()]);
+
+        env::log(
+            json!({
+                "type": "nft_revoke",
+                "params": {
+                    "token_id": token_id,
+                    "owner_id": owner_id,
+                    "account_id": account_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_released_storage(&owner_id, initial_storage_usage);
+    }
+
+    /// Refunds the released storage to the token owner
+    #[payable]
+    fn nft_revoke_all(&mut self, token_id: TokenId) {
+        assert_one_yocto();
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = self.assert_token_owner(&token_id);
+
+        if self.tokens.approvals_by_id.as_mut().unwrap().remove(&token_id).is_none() {
+            return;
+        }
+        self.approval_terms_by_id.remove(&token_id);
+
+        env::log(
+            json!({
+                "type": "nft_revoke_all",
+                "params": {
+                    "token_id": token_id,
+                    "owner_id": owner_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_released_storage(&owner_id, initial_storage_usage);
+    }
+
+    fn nft_is_approved(
+        &self,
+        token_id: TokenId,
+        approved_account_id: ValidAccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        self.tokens.owner_by_id.get(&token_id).expect("Token not found");
+        let actual_approval_id = match self
+            .tokens
+            .approvals_by_id
+            .as_ref()
+            .and_then(|by_id| by_id.get(&token_id))
+            .and_then(|approved_account_ids| approved_account_ids.get(approved_account_id.as_ref()).cloned())
+        {
+            Some(actual_approval_id) => actual_approval_id,
+            None => return false,
+        };
+        if self.internal_is_approval_expired(&token_id, approved_account_id.as_ref()) {
+            return false;
+        }
+        approval_id.map(|approval_id| approval_id == actual_approval_id).unwrap_or(true)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Approves every account in `account_ids` at once. With `msg`, each of them gets
+    /// `nft_on_approve`. Approvals stop authorizing transfers after `expires_at`, and with
+    /// `min_price` only `nft_transfer_payout` with at least that `balance` is allowed.
+    #[payable]
+    pub fn nft_approve_many(
+        &mut self,
+        token_id: TokenId,
+        account_ids: Vec<ValidAccountId>,
+        msg: Option<String>,
+        expires_at: Option<U64>,
+        min_price: Option<U128>,
+    ) -> Option<Promise> {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        self.assert_transferable(&token_id);
+        let owner_id = self.assert_token_owner(&token_id);
+        self.internal_clean_expired_approvals(&token_id, &owner_id);
+        let initial_storage_usage = env::storage_usage();
+
+        let promise = self.internal_approve(
+            &token_id,
+            &owner_id,
+            account_ids.into_iter().map(|account_id| account_id.into()).collect(),
+            msg,
+            expires_at.map(|expires_at| expires_at.0),
+            min_price.map(|min_price| min_price.0),
+            GAS_FOR_NFT_APPROVE,
+        );
+
+        // approving again can drop the previous terms
+        if env::storage_usage() > initial_storage_usage {
+            refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+        } else {
+            refund_released_storage(&owner_id, initial_storage_usage);
+        }
+
+        promise
+    }
+
+    /// Removes the expired approvals of `token_id` and refunds their storage to the owner.
+    /// Anyone can call it.
+    pub fn nft_clean_expired_approvals(&mut self, token_id: TokenId) {
+        let owner_id = self.tokens.owner_by_id.get(&token_id).expect("Token not found");
+        self.internal_clean_expired_approvals(&token_id, &owner_id);
+    }
+
+    /// None if `account_id` isn't approved or its approval expired
+    pub fn nft_approval_of(&self, token_id: TokenId, account_id: ValidAccountId) -> Option<ApprovalJson> {
+        let approval_id = self
+            .tokens
+            .approvals_by_id
+            .as_ref()
+            .and_then(|by_id| by_id.get(&token_id))
+            .and_then(|approved_account_ids| approved_account_ids.get(account_id.as_ref()).cloned())?;
+        if self.internal_is_approval_expired(&token_id, account_id.as_ref()) {
+            return None;
+        }
+
+        let terms = self
+            .approval_terms_by_id
+            .get(&token_id)
+            .and_then(|mut terms| terms.remove(account_id.as_ref()));
+        let expires_at = terms.as_ref().and_then(|terms| terms.expires_at);
+        Some(ApprovalJson {
+            approval_id,
+            expires_at,
+            remaining: expires_at.map(|expires_at| U64::from(expires_at.0 - env::block_timestamp())),
+            min_price: terms.and_then(|terms| terms.min_price),
+        })
+    }
+
+    /// Lets `operator_id` transfer every token of the caller, or only those of
+    /// `token_series_id`, without per-token approvals. Approving again replaces the scope.
+    /// Revoking refunds the released storage.
+    #[payable]
+    pub fn nft_set_approval_for_all(
+        &mut self,
+        operator_id: ValidAccountId,
+        approved: bool,
+        msg: Option<String>,
+        token_series_id: Option<TokenSeriesId>,
+    ) -> Option<Promise> {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+        let operator_id: AccountId = operator_id.into();
+        assert_ne!(owner_id, operator_id, "Marble: can't approve self as operator");
+
+        let mut operators = self.operators_by_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedMap::new(StorageKey::OperatorsByOwnerInner {
+                account_hash: env::sha256(owner_id.as_bytes()),
+            })
+        });
+        if approved {
+            if let Some(token_series_id) = &token_series_id {
+                assert!(
+                    self.token_series_by_id.get(token_series_id).is_some(),
+                    "Marble: token series {} not exist",
+                    token_series_id
+                );
+            }
+            operators.insert(&operator_id, &token_series_id);
+        } else {
+            operators.remove(&operator_id);
+        }
+        if operators.is_empty() {
+            self.operators_by_owner.remove(&owner_id);
+        } else {
+            self.operators_by_owner.insert(&owner_id, &operators);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_set_approval_for_all",
+                "params": {
+                    "owner_id": owner_id,
+                    "operator_id": operator_id,
+                    "approved": approved,
+                    "token_series_id": token_series_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        if env::storage_usage() > initial_storage_usage {
+            refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+        } else {
+            refund_released_storage(&owner_id, initial_storage_usage);
+        }
+
+        msg.map(|msg| {
+            ext_operator_receiver::nft_on_approval_for_all(
+                owner_id,
+                approved,
+                token_series_id,
+                msg,
+                &operator_id,
+                NO_DEPOSIT,
+                env::prepaid_gas() - GAS_FOR_NFT_APPROVE,
+            )
+        })
+    }
+
+    /// Without `token_series_id`, true only for operators of every series
+    pub fn nft_is_approved_for_all(
+        &self,
+        owner_id: ValidAccountId,
+        operator_id: ValidAccountId,
+        token_series_id: Option<TokenSeriesId>,
+    ) -> bool {
+        match self
+            .operators_by_owner
+            .get(owner_id.as_ref())
+            .and_then(|operators| operators.get(operator_id.as_ref()))
+        {
+            Some(None) => true,
+            Some(scope) => scope == token_series_id,
+            None => false,
+        }
+    }
+
+    pub fn nft_operators_of(
+        &self,
+        owner_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<OperatorJson> {
+        let operators = match self.operators_by_owner.get(owner_id.as_ref()) {
+            Some(operators) => operators,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), operators.len());
+
+        let operator_ids = operators.keys_as_vector();
+        let scopes = operators.values_as_vector();
+        (start_index..end_index)
+            .map(|index| OperatorJson {
+                operator_id: operator_ids.get(index).unwrap(),
+                token_series_id: scopes.get(index).unwrap(),
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Approves `account_ids` for `token_id` on behalf of `owner_id`, logs `nft_approve` and,
+    /// with `msg`, calls `nft_on_approve` on each approved account with the gas left after
+    /// `gas_reserved`. The caller charges the storage.
+    pub(crate) fn internal_approve(
+        &mut self,
+        token_id: &TokenId,
+        owner_id: &AccountId,
+        account_ids: Vec<AccountId>,
+        msg: Option<String>,
+        expires_at: Option<u64>,
+        min_price: Option<Balance>,
+        gas_reserved: Gas,
+    ) -> Option<Promise> {
+        assert!(!account_ids.is_empty(), "Marble: account_ids is empty");
+        assert!(
+            account_ids.len() <= MAX_ACCOUNTS_PER_APPROVE,
+            "Marble: exceeds {} accounts per approve",
+            MAX_ACCOUNTS_PER_APPROVE
+        );
+        if let Some(expires_at) = expires_at {
+            assert!(expires_at > env::block_timestamp(), "Marble: expires_at must be in the future");
+        }
+
+        let approvals_by_id = self.tokens.approvals_by_id.as_mut().unwrap();
+        let next_approval_id_by_id = self.tokens.next_approval_id_by_id.as_mut().unwrap();
+        let mut approved_account_ids = approvals_by_id.get(token_id).unwrap_or_default();
+        let mut approval_id: u64 = next_approval_id_by_id.get(token_id).unwrap_or(1);
+
+        let mut approvals: Vec<(AccountId, u64)> = vec![];
+        for account_id in account_ids {
+            approved_account_ids.insert(account_id.clone(), approval_id);
+            approvals.push((account_id, approval_id));
+            approval_id += 1;
+        }
+        approvals_by_id.insert(token_id, &approved_account_ids);
+        next_approval_id_by_id.insert(token_id, &approval_id);
+
+        // approving again without terms drops the previous ones
+        let account_ids: Vec<AccountId> = approvals.iter().map(|(account_id, _)| account_id.clone()).collect();
+        self.internal_remove_approval_terms(token_id, &account_ids);
+        if expires_at.is_some() || min_price.is_some() {
+            let mut terms = self.approval_terms_by_id.get(token_id).unwrap_or_default();
+            for account_id in account_ids {
+                terms.insert(account_id, ApprovalTerms {
+                    expires_at: expires_at.map(U64::from),
+                    min_price: min_price.map(U128::from),
+                });
+            }
+            self.approval_terms_by_id.insert(token_id, &terms);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_approve",
+                "params": {
+                    "token_id": token_id,
+                    "owner_id": owner_id,
+                    "approvals": approvals
+                        .iter()
+                        .map(|(account_id, approval_id)| json!({
+                            "account_id": account_id,
+                            "approval_id": approval_id,
+                        }))
+                        .collect::<Vec<_>>(),
+                    "expires_at": expires_at.map(U64::from),
+                    "min_price": min_price.map(U128::from),
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        let msg = msg?;
+        let gas_per_call = (env::prepaid_gas() - gas_reserved) / approvals.len() as Gas;
+        let mut promises = approvals.into_iter().map(|(account_id, approval_id)| {
+            ext_approval_receiver::nft_on_approve(
+                token_id.clone(),
+                owner_id.clone(),
+                approval_id,
+                msg.clone(),
+                &account_id,
+                NO_DEPOSIT,
+                gas_per_call,
+            )
+        });
+        let first = promises.next()?;
+        Some(promises.fold(first, |promise, next| promise.and(next)))
+    }
+
+    /// Expired approvals and sales below an approval's `min_price` don't authorize transfers.
+    /// `balance` is the sale price, None outside `nft_transfer_payout`.
+    pub(crate) fn assert_approval_terms(&self, token_id: &TokenId, account_id: &AccountId, balance: Option<Balance>) {
+        let terms = match self.approval_terms_by_id.get(token_id).and_then(|mut terms| terms.remove(account_id)) {
+            Some(terms) => terms,
+            None => return,
+        };
+        assert!(!terms.is_expired(), "Marble: approval expired");
+        if let Some(min_price) = terms.min_price {
+            let balance = balance.expect("Marble: approval requires nft_transfer_payout");
+            assert!(balance >= min_price.0, "Marble: balance is below approval min_price");
+        }
+    }
+
+    pub(crate) fn internal_is_approval_expired(&self, token_id: &TokenId, account_id: &AccountId) -> bool {
+        self.approval_terms_by_id
+            .get(token_id)
+            .and_then(|terms| terms.get(account_id).map(|terms| terms.is_expired()))
+            .unwrap_or(false)
+    }
+
+    fn internal_remove_approval_terms(&mut self, token_id: &TokenId, account_ids: &[AccountId]) {
+        let mut terms = match self.approval_terms_by_id.get(token_id) {
+            Some(terms) => terms,
+            None => return,
+        };
+        for account_id in account_ids {
+            terms.remove(account_id);
+        }
+        if terms.is_empty() {
+            self.approval_terms_by_id.remove(token_id);
+        } else {
+            self.approval_terms_by_id.insert(token_id, &terms);
+        }
+    }
+
+    fn internal_clean_expired_approvals(&mut self, token_id: &TokenId, owner_id: &AccountId) {
+        let expired: Vec<AccountId> = match self.approval_terms_by_id.get(token_id) {
+            Some(terms) => terms
+                .into_iter()
+                .filter(|(_, terms)| terms.is_expired())
+                .map(|(account_id, _)| account_id)
+                .collect(),
+            None => return,
+        };
+        if expired.is_empty() {
+            return;
+        }
+        let initial_storage_usage = env::storage_usage();
+
+        let approvals_by_id = self.tokens.approvals_by_id.as_mut().unwrap();
+        let mut approved_account_ids = approvals_by_id.get(token_id).unwrap_or_default();
+        for account_id in expired.iter() {
+            approved_account_ids.remove(account_id);
+        }
+        if approved_account_ids.is_empty() {
+            approvals_by_id.remove(token_id);
+        } else {
+            approvals_by_id.insert(token_id, &approved_account_ids);
+        }
+        self.internal_remove_approval_terms(token_id, &expired);
+
+        env::log(
+            json!({
+                "type": "nft_clean_expired_approvals",
+                "params": {
+                    "token_id": token_id,
+                    "owner_id": owner_id,
+                    "account_ids": expired,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_released_storage(owner_id, initial_storage_usage);
+    }
+
+    /// Whether `operator_id` may move `token_id` on behalf of `owner_id`
+    pub(crate) fn internal_is_operator(&self, owner_id: &AccountId, operator_id: &AccountId, token_id: &TokenId) -> bool {
+        match self.operators_by_owner.get(owner_id).and_then(|operators| operators.get(operator_id)) {
+            Some(Some(token_series_id)) => token_series_id == get_token_series_id(token_id),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// The caller must own `token_id`, be its operator or hold an unexpired approval on it
+    pub(crate) fn assert_owner_or_approved(&self, token_id: &TokenId) -> AccountId {
+        let owner_id = self.tokens.owner_by_id.get(token_id).expect("Token not found");
+        let predecessor_account_id = env::predecessor_account_id();
+        if predecessor_account_id != owner_id && !self.internal_is_operator(&owner_id, &predecessor_account_id, token_id) {
+            let is_approved = self
+                .tokens
+                .approvals_by_id
+                .as_ref()
+                .and_then(|by_id| by_id.get(token_id))
+                .map(|approvals| approvals.contains_key(&predecessor_account_id))
+                .unwrap_or(false);
+            assert!(
+                is_approved && !self.internal_is_approval_expired(token_id, &predecessor_account_id),
+                "Marble: Owner or approved account only"
+            );
+        }
+        owner_id
+    }
+
+    fn assert_token_owner(&self, token_id: &TokenId) -> AccountId {
+        let owner_id = self.tokens.owner_by_id.get(token_id).expect("Token not found");
+        assert_eq!(env::predecessor_account_id(), owner_id, "Token owner only");
+        owner_id
+    }
+}
+
+impl ApprovalTerms {
+    fn is_expired(&self) -> bool {
+        self.expires_at.map(|expires_at| expires_at.0 <= env::block_timestamp()).unwrap_or(false)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{create_series, setup_token, STORAGE_FOR_CREATE_SERIES, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, testing_env_with_promise_results};
+    use near_sdk::{MockedBlockchain, PromiseResult};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_approve_many_and_revoke() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3), accounts(4)], None, None, None);
+
+        assert!(contract.nft_is_approved(token_id.clone(), accounts(3), Some(1)));
+        assert!(contract.nft_is_approved(token_id.clone(), accounts(4), Some(2)));
+
+        contract.nft_approve(token_id.clone(), accounts(3), None);
+        assert!(!contract.nft_is_approved(token_id.clone(), accounts(3), Some(1)));
+        assert!(contract.nft_is_approved(token_id.clone(), accounts(3), Some(3)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_revoke(token_id.clone(), accounts(3));
+        assert!(!contract.nft_is_approved(token_id.clone(), accounts(3), None));
+        assert!(contract.nft_is_approved(token_id.clone(), accounts(4), None));
+
+        contract.nft_revoke_all(token_id.clone());
+        assert!(!contract.nft_is_approved(token_id, accounts(4), None));
+    }
+
+    #[test]
+    fn test_mint_and_approve_shares_approval_ids() {
+        let (mut context, mut contract, _) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_mint_and_approve("1".to_string(), accounts(3), None, None, None, None, None, None);
+        contract.nft_approve("1:2".to_string(), accounts(4), None);
+
+        assert!(contract.nft_is_approved("1:2".to_string(), accounts(3), Some(1)));
+        assert!(contract.nft_is_approved("1:2".to_string(), accounts(4), Some(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Token owner only")]
+    fn test_invalid_approve_not_owner() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_approve(token_id, accounts(3), None);
+    }
+
+    #[test]
+    fn test_set_approval_for_all_transfer() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_set_approval_for_all(accounts(3), true, None, Some("1".to_string()));
+
+        assert!(contract.nft_is_approved_for_all(accounts(2), accounts(3), Some("1".to_string())));
+        assert!(!contract.nft_is_approved_for_all(accounts(2), accounts(3), None));
+        assert_eq!(contract.nft_operators_of(accounts(2), None, None).len(), 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer(accounts(4), token_id.clone(), None, None);
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(4).to_string());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_set_approval_for_all(accounts(3), false, None, None);
+        assert!(contract.nft_operators_of(accounts(2), None, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn test_invalid_transfer_operator_of_other_series() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_set_approval_for_all(accounts(3), true, None, Some("2".to_string()));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer(accounts(4), token_id, None, None);
+    }
+
+    #[test]
+    fn test_expired_approval_ignored_and_cleaned() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(10)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3)], None, Some(U64::from(100)), None);
+        assert_eq!(contract.nft_approval_of(token_id.clone(), accounts(3)).unwrap().remaining, Some(U64::from(90)));
+
+        testing_env!(context.block_timestamp(100).build());
+        assert!(!contract.nft_is_approved(token_id.clone(), accounts(3), None));
+        assert!(contract.nft_approval_of(token_id.clone(), accounts(3)).is_none());
+        assert!(contract.nft_token(token_id.clone()).unwrap().approved_account_ids.unwrap().is_empty());
+
+        contract.nft_clean_expired_approvals(token_id.clone());
+        assert!(contract.approval_terms_by_id.get(&token_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: approval expired")]
+    fn test_invalid_transfer_expired_approval() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3)], None, Some(U64::from(100)), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .block_timestamp(100)
+            .build()
+        );
+        contract.nft_transfer(accounts(4), token_id, Some(1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: balance is below approval min_price")]
+    fn test_invalid_transfer_payout_below_min_price() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3)], None, None, Some(U128::from(1000)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer_payout(accounts(4), token_id, Some(1), Some(U128::from(999)), Some(10));
+    }
+
+    #[test]
+    fn test_reapprove_without_terms() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3)], None, Some(U64::from(100)), Some(U128::from(1000)));
+
+        // dropping the terms releases storage, only 1 yoctoNEAR is attached
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3)], None, None, None);
+
+        let approval = contract.nft_approval_of(token_id.clone(), accounts(3)).unwrap();
+        assert!(approval.expires_at.is_none());
+        assert!(approval.min_price.is_none());
+        assert!(contract.approval_terms_by_id.get(&token_id).is_none());
+    }
+
+    #[test]
+    fn test_transfer_call_revert_restores_approval_terms() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3)], None, None, Some(U128::from(1000)));
+        let old_approvals = contract.tokens.approvals_by_id.as_ref().unwrap().get(&token_id);
+        let old_approval_terms = contract.approval_terms_by_id.get(&token_id);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer_call(accounts(4), token_id.clone(), None, None, "".to_string());
+        assert!(contract.approval_terms_by_id.get(&token_id).is_none());
+
+        // the receiver asks for the token to be returned
+        testing_env_with_promise_results(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            PromiseResult::Successful(b"true".to_vec()),
+        );
+        assert!(!contract.nft_resolve_transfer(
+            accounts(2).to_string(),
+            accounts(4).to_string(),
+            token_id.clone(),
+            old_approvals,
+            old_approval_terms,
+        ));
+
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(2).to_string());
+        let approval = contract.nft_approval_of(token_id, accounts(3)).unwrap();
+        assert_eq!(approval.min_price, Some(U128::from(1000)));
+    }
+}
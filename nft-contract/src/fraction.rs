@@ -0,0 +1,507 @@
+use crate::*;
+use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FT_METADATA_SPEC};
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::PromiseResult;
+
+/// Room for the share contract's state next to its code: metadata and the first two accounts
+const SHARE_STATE_STORAGE: u64 = 1_000;
+const GAS_FOR_SHARE_INIT: Gas = 20_000_000_000_000;
+const GAS_FOR_RESOLVE_FRACTIONALIZE: Gas = 20_000_000_000_000;
+
+#[ext_contract(ext_fraction)]
+trait FractionResolver {
+    fn nft_resolve_fractionalize(&mut self, share_id: AccountId, deploy_cost: U128) -> bool;
+}
+
+/// A token locked in the contract against NEP-141 shares deployed to `share_id`, a sub-account
+/// of this contract. The token leaves the vault when every share is sent back with
+/// `ft_transfer_call` and msg "redeem", or on a buyout at `reserve_price`. After a buyout
+/// holders send their shares with msg "claim" for their part of the proceeds.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Vault {
+    token_id: TokenId,
+    owner_id: AccountId,
+    total_shares: Balance,
+    reserve_price: Balance,
+    /// Set once the share contract is deployed and initialized
+    deployed: bool,
+    bought_out_by: Option<AccountId>,
+    /// NEAR left to claim after a buyout, for `outstanding_shares`
+    proceeds: Balance,
+    outstanding_shares: Balance,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VaultJson {
+    share_id: AccountId,
+    token_id: TokenId,
+    owner_id: AccountId,
+    total_shares: U128,
+    reserve_price: U128,
+    deployed: bool,
+    bought_out_by: Option<AccountId>,
+    proceeds: U128,
+    outstanding_shares: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Stores the share contract wasm, passed as the raw input of the call. Owner only.
+    #[payable]
+    pub fn set_fraction_share_code(&mut self) {
+        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Marble: Owner only");
+        let initial_storage_usage = env::storage_usage();
+        let code = env::input().expect("Marble: share code is missing");
+        self.fraction_share_code.set(&code);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+    }
+
+    /// Locks `token_id` in the contract and deploys its `total_shares` NEP-141 shares, all held by
+    /// the caller. The caller pays for the vault and for the share account's storage.
+    #[payable]
+    pub fn nft_fractionalize(&mut self, token_id: TokenId, total_shares: U128, reserve_price: U128) -> Promise {
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+
+        let token_owner_id = self.tokens.owner_by_id.get(&token_id).expect("Token not found");
+        assert_eq!(token_owner_id, owner_id, "Token owner only");
+        assert!(self.vault_by_token.get(&token_id).is_none(), "Marble: token already fractionalized");
+        assert!(total_shares.0 > 0, "Marble: total_shares must be positive");
+        assert!(reserve_price.0 > 0, "Marble: reserve_price must be positive");
+
+        let code = self.fraction_share_code.get().expect("Marble: share code not set");
+        let deploy_cost = env::storage_byte_cost() * Balance::from(code.len() as u64 + SHARE_STATE_STORAGE);
+        assert!(
+            env::attached_deposit() >= deploy_cost,
+            "Marble: attach at least {} yoctoNEAR to deploy the shares",
+            deploy_cost
+        );
+
+        self.vault_nonce += 1;
+        let contract_id = env::current_account_id();
+        let share_id = format!("v{}.{}", self.vault_nonce, contract_id);
+        assert!(is_valid_account_id(share_id.as_bytes()), "Marble: invalid share account {}", share_id);
+
+        self.internal_transfer(&owner_id, &contract_id, &token_id, None, Some("fractionalize".to_string()));
+
+        let vault = Vault {
+            token_id: token_id.clone(),
+            owner_id: owner_id.clone(),
+            total_shares: total_shares.0,
+            reserve_price: reserve_price.0,
+            deployed: false,
+            bought_out_by: None,
+            proceeds: 0,
+            outstanding_shares: 0,
+        };
+        self.vaults.insert(&share_id, &vault);
+        self.vault_by_token.insert(&token_id, &share_id);
+
+        env::log(
+            json!({
+                "type": "nft_fractionalize",
+                "params": {
+                    "token_id": token_id,
+                    "share_id": share_id,
+                    "owner_id": owner_id,
+                    "total_shares": total_shares,
+                    "reserve_price": reserve_price,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+        NearEvent::log_nft_transfer(
+            owner_id.clone(),
+            contract_id.clone(),
+            vec![token_id.clone()],
+            Some("fractionalize".to_string()),
+            None,
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, deploy_cost);
+
+        let metadata = FungibleTokenMetadata {
+            spec: FT_METADATA_SPEC.to_string(),
+            name: format!("{} shares", token_id),
+            symbol: "SHARE".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 0,
+        };
+        Promise::new(share_id.clone())
+            .create_account()
+            .transfer(deploy_cost)
+            .deploy_contract(code)
+            .function_call(
+                b"new".to_vec(),
+                json!({
+                    "owner_id": owner_id,
+                    "total_supply": total_shares,
+                    "metadata": metadata,
+                })
+                    .to_string()
+                    .into_bytes(),
+                NO_DEPOSIT,
+                GAS_FOR_SHARE_INIT,
+            )
+            .then(ext_fraction::nft_resolve_fractionalize(
+                share_id,
+                U128::from(deploy_cost),
+                &contract_id,
+                NO_DEPOSIT,
+                GAS_FOR_RESOLVE_FRACTIONALIZE,
+            ))
+    }
+
+    /// Opens the vault once the shares are live. Otherwise returns the token and the deploy
+    /// deposit to the owner.
+    #[private]
+    pub fn nft_resolve_fractionalize(&mut self, share_id: AccountId, deploy_cost: U128) -> bool {
+        let initial_storage_usage = env::storage_usage();
+        let mut vault = self.vaults.get(&share_id).expect("Marble: vault not exist");
+
+        let deployed = env::promise_results_count() == 1
+            && matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if deployed {
+            vault.deployed = true;
+            self.vaults.insert(&share_id, &vault);
+        } else {
+            self.vaults.remove(&share_id);
+            self.vault_by_token.remove(&vault.token_id);
+            self.internal_release_vault_token(&vault.token_id, &vault.owner_id, "fractionalize_failed");
+            Promise::new(vault.owner_id.clone()).transfer(deploy_cost.0);
+            refund_released_storage(&vault.owner_id, initial_storage_usage);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_resolve_fractionalize",
+                "params": {
+                    "token_id": vault.token_id,
+                    "share_id": share_id,
+                    "deployed": deployed,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        deployed
+    }
+
+    /// Buys the token at the reserve price. Royalty is sent out and the remainder is claimable by
+    /// the share holders, the buyer included. The token can be fractionalized again right away.
+    #[payable]
+    pub fn nft_buyout(&mut self, token_id: TokenId) -> Payout {
+        let buyer_id = env::predecessor_account_id();
+
+        let share_id = self.vault_by_token.get(&token_id).expect("Marble: vault not exist");
+        let mut vault = self.vaults.get(&share_id).expect("Marble: vault not exist");
+        assert!(vault.deployed, "Marble: shares are not deployed yet");
+        assert!(vault.bought_out_by.is_none(), "Marble: vault is bought out");
+
+        let price = vault.reserve_price;
+        assert!(
+            env::attached_deposit() >= price,
+            "Marble: attached deposit is less than buyout price {}",
+            price
+        );
+
+        let mut payout: Payout = Payout { payout: HashMap::new() };
+        let mut total_royalty: Balance = 0;
+        for (account_id, value) in self.internal_get_royalty(&token_id).iter() {
+            let amount = royalty_to_payout(*value, price);
+            if amount.0 > 0 {
+                Promise::new(account_id.clone()).transfer(amount.0);
+                total_royalty += amount.0;
+                payout.payout.insert(account_id.clone(), amount);
+            }
+        }
+
+        vault.bought_out_by = Some(buyer_id.clone());
+        vault.proceeds = price - total_royalty;
+        vault.outstanding_shares = vault.total_shares;
+        self.vaults.insert(&share_id, &vault);
+        self.vault_by_token.remove(&token_id);
+
+        let refund = env::attached_deposit() - price;
+        if refund > 1 {
+            Promise::new(buyer_id.clone()).transfer(refund);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_buyout",
+                "params": {
+                    "token_id": token_id,
+                    "share_id": share_id,
+                    "buyer_id": buyer_id,
+                    "price": U128::from(price),
+                    "royalty": payout.payout,
+                    "proceeds": U128::from(vault.proceeds),
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        self.internal_release_vault_token(&token_id, &buyer_id, "buyout");
+
+        payout
+    }
+
+    /// Open vault of `token_id`
+    pub fn nft_get_vault(&self, token_id: TokenId) -> Option<VaultJson> {
+        let share_id = self.vault_by_token.get(&token_id)?;
+        self.vaults.get(&share_id).map(|vault| vault_to_json(share_id, vault))
+    }
+
+    /// Vault of the shares at `share_id`, kept after a buyout until every share is claimed
+    pub fn nft_get_fraction_vault(&self, share_id: ValidAccountId) -> Option<VaultJson> {
+        let share_id: AccountId = share_id.into();
+        self.vaults.get(&share_id).map(|vault| vault_to_json(share_id, vault))
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract {
+    /// Shares sent back to their vault: msg "redeem" with every share takes the token, msg "claim"
+    /// after a buyout pays out the sender's part of the proceeds. The shares stay locked here.
+    fn ft_on_transfer(&mut self, sender_id: ValidAccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        let share_id = env::predecessor_account_id();
+        let mut vault = self.vaults.get(&share_id).expect("Marble: vault not exist");
+        let sender_id: AccountId = sender_id.into();
+
+        match msg.as_str() {
+            "redeem" => {
+                assert!(vault.bought_out_by.is_none(), "Marble: vault is bought out");
+                assert_eq!(amount.0, vault.total_shares, "Marble: redeem requires every share");
+                self.vaults.remove(&share_id);
+                self.vault_by_token.remove(&vault.token_id);
+
+                self.internal_release_vault_token(&vault.token_id, &sender_id, "redeem_fraction");
+            }
+            "claim" => {
+                assert!(vault.bought_out_by.is_some(), "Marble: vault is not bought out");
+                assert!(amount.0 <= vault.outstanding_shares, "Marble: not enough outstanding shares");
+
+                let claimed = vault.proceeds * amount.0 / vault.outstanding_shares;
+                vault.proceeds -= claimed;
+                vault.outstanding_shares -= amount.0;
+                if vault.outstanding_shares == 0 {
+                    self.vaults.remove(&share_id);
+                } else {
+                    self.vaults.insert(&share_id, &vault);
+                }
+
+                if claimed > 0 {
+                    Promise::new(sender_id.clone()).transfer(claimed);
+                }
+
+                env::log(
+                    json!({
+                        "type": "fraction_claim_proceeds",
+                        "params": {
+                            "token_id": vault.token_id,
+                            "share_id": share_id,
+                            "account_id": sender_id,
+                            "shares": amount,
+                            "amount": U128::from(claimed),
+                        }
+                    })
+                        .to_string()
+                        .as_bytes(),
+                );
+            }
+            _ => env::panic(b"Marble: msg must be redeem or claim"),
+        }
+
+        PromiseOrValue::Value(U128::from(0))
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_not_vaulted(&self, token_id: &TokenId) {
+        assert!(self.vault_by_token.get(token_id).is_none(), "Marble: token is fractionalized");
+    }
+
+    fn internal_release_vault_token(&mut self, token_id: &TokenId, receiver_id: &AccountId, memo: &str) {
+        let contract_id = env::current_account_id();
+        self.internal_release_custody(receiver_id, token_id, Some(memo.to_string()));
+        NearEvent::log_nft_transfer(
+            contract_id,
+            receiver_id.clone(),
+            vec![token_id.clone()],
+            Some(memo.to_string()),
+            None,
+        );
+    }
+}
+
+fn vault_to_json(share_id: AccountId, vault: Vault) -> VaultJson {
+    VaultJson {
+        share_id,
+        token_id: vault.token_id,
+        owner_id: vault.owner_id,
+        total_shares: U128::from(vault.total_shares),
+        reserve_price: U128::from(vault.reserve_price),
+        deployed: vault.deployed,
+        bought_out_by: vault.bought_out_by,
+        proceeds: U128::from(vault.proceeds),
+        outstanding_shares: U128::from(vault.outstanding_shares),
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::setup_token;
+    use near_sdk::test_utils::{accounts, testing_env_with_promise_results, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    const SHARE_CODE: &[u8] = b"share wasm";
+
+    fn deploy_cost() -> Balance {
+        env::storage_byte_cost() * Balance::from(SHARE_CODE.len() as u64 + SHARE_STATE_STORAGE)
+    }
+
+    /// accounts(2) fractionalizes a token with 10% royalty to accounts(1) into 100 shares.
+    /// Returns the share contract id once its deploy resolved with `result`.
+    fn setup_vault(result: PromiseResult) -> (VMContextBuilder, Contract, TokenId, AccountId) {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        let mut upload = context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(deploy_cost())
+            .build();
+        upload.input = SHARE_CODE.to_vec();
+        testing_env!(upload);
+        contract.set_fraction_share_code();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(deploy_cost() * 2)
+            .build()
+        );
+        contract.nft_fractionalize(token_id.clone(), U128::from(100), U128::from(10_000_000));
+        let share_id = contract.nft_get_vault(token_id.clone()).unwrap().share_id;
+
+        testing_env_with_promise_results(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            result,
+        );
+        contract.nft_resolve_fractionalize(share_id.clone(), U128::from(deploy_cost()));
+
+        (context, contract, token_id, share_id)
+    }
+
+    #[test]
+    fn test_fractionalize_and_buyout() {
+        let (mut context, mut contract, token_id, share_id) = setup_vault(PromiseResult::Successful(vec![]));
+        assert_eq!(share_id, format!("v1.{}", accounts(0)));
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(0).to_string());
+        assert!(contract.nft_get_vault(token_id.clone()).unwrap().deployed);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(10_000_000)
+            .build()
+        );
+        let payout = contract.nft_buyout(token_id.clone());
+        assert_eq!(payout.payout.get(&accounts(1).to_string()).unwrap(), &U128::from(1_000_000));
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(3).to_string());
+        assert!(contract.nft_get_vault(token_id.clone()).is_none());
+
+        // holders claim by sending their shares back through the share contract
+        testing_env!(context
+            .predecessor_account_id(share_id.clone().try_into().unwrap())
+            .attached_deposit(1)
+            .build()
+        );
+        contract.ft_on_transfer(accounts(2), U128::from(60), "claim".to_string());
+        let vault = contract.nft_get_fraction_vault(share_id.clone().try_into().unwrap()).unwrap();
+        assert_eq!(vault.proceeds, U128::from(3_600_000));
+        assert_eq!(vault.outstanding_shares, U128::from(40));
+
+        contract.ft_on_transfer(accounts(3), U128::from(40), "claim".to_string());
+        assert!(contract.nft_get_fraction_vault(share_id.try_into().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_fractionalize_again_before_claims() {
+        let (mut context, mut contract, token_id, share_id) = setup_vault(PromiseResult::Successful(vec![]));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(10_000_000)
+            .build()
+        );
+        contract.nft_buyout(token_id.clone());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(deploy_cost() * 2)
+            .build()
+        );
+        contract.nft_fractionalize(token_id.clone(), U128::from(10), U128::from(20_000_000));
+
+        let vault = contract.nft_get_vault(token_id).unwrap();
+        assert_eq!(vault.share_id, format!("v2.{}", accounts(0)));
+        assert_eq!(vault.owner_id, accounts(3).to_string());
+        assert!(contract.nft_get_fraction_vault(share_id.try_into().unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_redeem_fraction_with_all_shares() {
+        let (mut context, mut contract, token_id, share_id) = setup_vault(PromiseResult::Successful(vec![]));
+
+        testing_env!(context
+            .predecessor_account_id(share_id.try_into().unwrap())
+            .attached_deposit(1)
+            .build()
+        );
+        contract.ft_on_transfer(accounts(3), U128::from(100), "redeem".to_string());
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(3).to_string());
+        assert!(contract.nft_get_vault(token_id).is_none());
+    }
+
+    #[test]
+    fn test_failed_share_deploy_returns_token() {
+        let (_, contract, token_id, share_id) = setup_vault(PromiseResult::Failed);
+
+        assert_eq!(contract.nft_token(token_id.clone()).unwrap().owner_id, accounts(2).to_string());
+        assert!(contract.nft_get_vault(token_id).is_none());
+        assert!(contract.nft_get_fraction_vault(share_id.try_into().unwrap()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: redeem requires every share")]
+    fn test_invalid_redeem_fraction_partial() {
+        let (mut context, mut contract, _, share_id) = setup_vault(PromiseResult::Successful(vec![]));
+
+        testing_env!(context
+            .predecessor_account_id(share_id.try_into().unwrap())
+            .attached_deposit(1)
+            .build()
+        );
+        contract.ft_on_transfer(accounts(2), U128::from(60), "redeem".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: vault not exist")]
+    fn test_invalid_shares_from_other_contract() {
+        let (mut context, mut contract, _, _) = setup_vault(PromiseResult::Successful(vec![]));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.ft_on_transfer(accounts(3), U128::from(100), "redeem".to_string());
+    }
+}
@@ -0,0 +1,350 @@
+use crate::*;
+use std::collections::HashSet;
+
+pub type ProposalId = String;
+
+const MAX_PROPOSAL_OPTIONS: usize = 10;
+const MAX_VOTE_TOKENS: usize = 100;
+
+/// One vote per token id, counted for the option it was cast for
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Proposal {
+    proposer_id: AccountId,
+    title: String,
+    description: Option<String>,
+    options: Vec<String>,
+    starts_at: u64,
+    ends_at: u64,
+    token_series_ids: Vec<TokenSeriesId>,
+    tally: Vec<u64>,
+    votes: LookupMap<TokenId, u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ProposalJson {
+    proposal_id: ProposalId,
+    proposer_id: AccountId,
+    title: String,
+    description: Option<String>,
+    options: Vec<String>,
+    starts_at: U64,
+    ends_at: U64,
+    token_series_ids: Vec<TokenSeriesId>,
+    tally: Vec<U64>,
+    is_ended: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Tokens an account must hold across a proposal's series to create it. None leaves
+    /// proposals to the contract owner.
+    #[payable]
+    pub fn set_proposal_threshold(&mut self, threshold: Option<U64>) {
+        assert_one_yocto();
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Only owner"
+        );
+        self.proposal_threshold = threshold.map(|threshold| threshold.0);
+    }
+
+    pub fn get_proposal_threshold(&self) -> Option<U64> {
+        self.proposal_threshold.map(U64::from)
+    }
+
+    /// Holders of `token_series_ids` vote from `starts_at` until `ends_at` (nanoseconds)
+    #[payable]
+    pub fn nft_create_proposal(
+        &mut self,
+        title: String,
+        description: Option<String>,
+        options: Vec<String>,
+        starts_at: U64,
+        ends_at: U64,
+        token_series_ids: Vec<TokenSeriesId>,
+    ) -> ProposalJson {
+        let initial_storage_usage = env::storage_usage();
+        let proposer_id = env::predecessor_account_id();
+
+        assert!(!title.is_empty(), "Marble: title is required");
+        assert!(
+            options.len() >= 2 && options.len() <= MAX_PROPOSAL_OPTIONS,
+            "Marble: proposal requires 2 to {} options",
+            MAX_PROPOSAL_OPTIONS
+        );
+        assert!(starts_at.0 < ends_at.0, "Marble: starts_at must be before ends_at");
+        assert!(ends_at.0 > env::block_timestamp(), "Marble: ends_at must be in the future");
+        assert!(!token_series_ids.is_empty(), "Marble: proposal requires token_series_ids");
+        let unique_token_series_ids: HashSet<&TokenSeriesId> = token_series_ids.iter().collect();
+        assert_eq!(unique_token_series_ids.len(), token_series_ids.len(), "Marble: duplicate token_series_ids");
+        for token_series_id in token_series_ids.iter() {
+            assert!(
+                self.token_series_by_id.get(token_series_id).is_some(),
+                "Marble: token series {} not exist",
+                token_series_id
+            );
+        }
+
+        if proposer_id != self.tokens.owner_id {
+            let threshold = self.proposal_threshold.expect("Marble: Only owner");
+            let held: u64 = token_series_ids
+                .iter()
+                .filter_map(|token_series_id| self.owners_by_series.get(token_series_id))
+                .filter_map(|owners| owners.get(&proposer_id))
+                .sum();
+            assert!(held >= threshold, "Marble: holds less than proposal threshold of {} tokens", threshold);
+        }
+
+        let proposal_id = format!("{}", self.proposals.len() + 1);
+        let proposal = Proposal {
+            proposer_id,
+            title,
+            description,
+            tally: vec![0; options.len()],
+            options,
+            starts_at: starts_at.0,
+            ends_at: ends_at.0,
+            token_series_ids,
+            votes: LookupMap::new(StorageKey::ProposalVotes {
+                proposal_id: proposal_id.clone(),
+            }),
+        };
+        self.proposals.insert(&proposal_id, &proposal);
+
+        let proposal_json = proposal_to_json(proposal_id, proposal);
+        env::log(
+            json!({
+                "type": "nft_create_proposal",
+                "params": proposal_json,
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+
+        proposal_json
+    }
+
+    /// Casts one vote for `option_index` per token of the caller. A token that already voted
+    /// on the proposal can't vote again, whoever holds it now.
+    #[payable]
+    pub fn nft_vote(&mut self, proposal_id: ProposalId, option_index: u32, token_ids: Vec<TokenId>) {
+        let initial_storage_usage = env::storage_usage();
+        let voter_id = env::predecessor_account_id();
+
+        let mut proposal = self.proposals.get(&proposal_id).expect("Marble: proposal not exist");
+        let now = env::block_timestamp();
+        assert!(now >= proposal.starts_at, "Marble: voting has not started");
+        assert!(now < proposal.ends_at, "Marble: voting has ended");
+        assert!((option_index as usize) < proposal.options.len(), "Marble: invalid option_index");
+        assert!(!token_ids.is_empty(), "Marble: token_ids is empty");
+        assert!(
+            token_ids.len() <= MAX_VOTE_TOKENS,
+            "Marble: exceeds {} tokens per vote",
+            MAX_VOTE_TOKENS
+        );
+
+        for token_id in token_ids.iter() {
+            let owner_id = self.tokens.owner_by_id.get(token_id).expect("Token not found");
+            assert_eq!(owner_id, voter_id, "Token owner only");
+            assert!(
+                proposal.token_series_ids.contains(&get_token_series_id(token_id)),
+                "Marble: token {} is not eligible",
+                token_id
+            );
+            assert!(proposal.votes.get(token_id).is_none(), "Marble: token {} already voted", token_id);
+            proposal.votes.insert(token_id, &option_index);
+        }
+        proposal.tally[option_index as usize] += token_ids.len() as u64;
+        self.proposals.insert(&proposal_id, &proposal);
+
+        env::log(
+            json!({
+                "type": "nft_vote",
+                "params": {
+                    "proposal_id": proposal_id,
+                    "voter_id": voter_id,
+                    "option_index": option_index,
+                    "token_ids": token_ids,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+    }
+
+    pub fn nft_get_proposal(&self, proposal_id: ProposalId) -> Option<ProposalJson> {
+        self.proposals.get(&proposal_id).map(|proposal| proposal_to_json(proposal_id, proposal))
+    }
+
+    pub fn nft_get_proposals(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<ProposalJson> {
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), self.proposals.len());
+
+        let keys = self.proposals.keys_as_vector();
+        let values = self.proposals.values_as_vector();
+        (start_index..end_index)
+            .map(|index| proposal_to_json(keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect()
+    }
+
+    /// Option index `token_id` voted for, None if it hasn't voted
+    pub fn nft_vote_of(&self, proposal_id: ProposalId, token_id: TokenId) -> Option<u32> {
+        self.proposals.get(&proposal_id).and_then(|proposal| proposal.votes.get(&token_id))
+    }
+}
+
+fn proposal_to_json(proposal_id: ProposalId, proposal: Proposal) -> ProposalJson {
+    ProposalJson {
+        proposal_id,
+        proposer_id: proposal.proposer_id,
+        title: proposal.title,
+        description: proposal.description,
+        options: proposal.options,
+        starts_at: U64::from(proposal.starts_at),
+        ends_at: U64::from(proposal.ends_at),
+        token_series_ids: proposal.token_series_ids,
+        tally: proposal.tally.into_iter().map(U64::from).collect(),
+        is_ended: env::block_timestamp() >= proposal.ends_at,
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{mint_token, setup_series, STORAGE_FOR_CREATE_SERIES, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    /// accounts(2) holds two tokens of series "1", proposal "1" runs from 100 to 200
+    fn setup_proposal() -> (VMContextBuilder, Contract, Vec<TokenId>) {
+        let (mut context, mut contract) = setup_series();
+        contract.nft_create_proposal(
+            "Next chapter".to_string(),
+            None,
+            vec!["Heroes".to_string(), "Villains".to_string()],
+            U64::from(100),
+            U64::from(200),
+            vec!["1".to_string()],
+        );
+
+        let token_ids = (0..2)
+            .map(|_| mint_token(&mut context, &mut contract, "1", accounts(2)))
+            .collect();
+
+        (context, contract, token_ids)
+    }
+
+    #[test]
+    fn test_vote_and_tally() {
+        let (mut context, mut contract, token_ids) = setup_proposal();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(150)
+            .build()
+        );
+        contract.nft_vote("1".to_string(), 1, vec![token_ids[0].clone()]);
+
+        assert_eq!(contract.nft_vote_of("1".to_string(), token_ids[0].clone()), Some(1));
+        assert_eq!(contract.nft_vote_of("1".to_string(), token_ids[1].clone()), None);
+
+        contract.nft_vote("1".to_string(), 0, vec![token_ids[1].clone()]);
+
+        testing_env!(context.block_timestamp(200).build());
+        let proposal = contract.nft_get_proposal("1".to_string()).unwrap();
+        assert_eq!(proposal.tally, vec![U64::from(1), U64::from(1)]);
+        assert!(proposal.is_ended);
+    }
+
+    #[test]
+    #[should_panic(expected = "already voted")]
+    fn test_invalid_vote_after_transfer() {
+        let (mut context, mut contract, token_ids) = setup_proposal();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(150)
+            .build()
+        );
+        contract.nft_vote("1".to_string(), 1, vec![token_ids[0].clone()]);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer(accounts(3), token_ids[0].clone(), None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_vote("1".to_string(), 0, vec![token_ids[0].clone()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: holds less than proposal threshold of 3 tokens")]
+    fn test_invalid_create_proposal_below_threshold() {
+        let (mut context, mut contract, _) = setup_proposal();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.set_proposal_threshold(Some(U64::from(3)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        contract.nft_create_proposal(
+            "More pages".to_string(),
+            None,
+            vec!["Yes".to_string(), "No".to_string()],
+            U64::from(100),
+            U64::from(200),
+            vec!["1".to_string()],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: duplicate token_series_ids")]
+    fn test_invalid_create_proposal_duplicate_series() {
+        let (mut context, mut contract, _) = setup_proposal();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.set_proposal_threshold(Some(U64::from(3)));
+
+        // two tokens of series "1" listed three times would pass a threshold of 3
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        contract.nft_create_proposal(
+            "More pages".to_string(),
+            None,
+            vec!["Yes".to_string(), "No".to_string()],
+            U64::from(100),
+            U64::from(200),
+            vec!["1".to_string(), "1".to_string(), "1".to_string()],
+        );
+    }
+}
@@ -5,10 +5,10 @@ use near_contract_standards::non_fungible_token::metadata::{
     NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata, NFT_METADATA_SPEC,
 };
 use near_contract_standards::non_fungible_token::NonFungibleToken;
-use near_contract_standards::non_fungible_token::{Token, TokenId};
+use near_contract_standards::non_fungible_token::TokenId;
 use near_sdk::Metadata;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::collections::{LazyOption, LookupMap, TreeMap, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::{ValidAccountId, U128, U64};
 use near_sdk::{
     assert_one_yocto, env, near_bindgen, serde_json::json, AccountId, Balance, BorshStorageKey,
@@ -16,17 +16,41 @@ use near_sdk::{
 };
 use near_sdk::serde::{Deserialize, Serialize};
 use std::collections::{HashMap};
+use std::ops::Bound;
 use near_sdk::env::{is_valid_account_id};
 
+pub mod approval;
 pub mod event;
-
+pub mod fraction;
+pub mod governance;
+pub mod nesting;
+pub mod snapshot;
+pub mod staking;
+pub mod recipe;
+pub mod rental;
+pub mod series_metadata;
+pub mod token_traits;
+pub mod trusted_operator;
+pub mod validity;
+
+pub use approval::{ApprovalJson, ApprovalTerms, OperatorJson};
 pub use event::NearEvent;
+pub use fraction::{Vault, VaultJson};
+pub use governance::{Proposal, ProposalId, ProposalJson};
+pub use recipe::{Recipe, RecipeId, RecipeJson};
+pub use rental::{TokenUser, TokenUserJson};
+pub use series_metadata::{SeriesMetadataVersion, SeriesMetadataVersionJson};
+pub use snapshot::{HolderSnapshot, SnapshotId};
+pub use staking::{Stake, StakeJson};
+pub use token_traits::Traits;
+pub use validity::{SeriesValidity, ValidityWindow};
+use validity::stamp_validity;
 
 /// between token_series_id and edition number e.g. 42:2 where 42 is series and 2 is edition
 pub const TOKEN_DELIMETER: char = ':';
-/// TokenMetadata.title returned for individual token e.g. "Title — 2/10" where 10 is max copies
+/// TokenMetadata.title returned for individual token e.g. "Title #2"
 pub const TITLE_DELIMETER: &str = " #";
-/// e.g. "Title — 2/10" where 10 is max copies
+/// e.g. "Title #2/10" where 10 is max copies, see `TitleFormat::EditionOfCopies`
 pub const EDITION_DELIMETER: &str = "/";
 pub const TREASURY_FEE: u128 = 500; // 500 / 10_000 = 0.05
 
@@ -35,6 +59,9 @@ const GAS_FOR_NFT_TRANSFER_CALL: Gas = 30_000_000_000_000 + GAS_FOR_RESOLVE_TRAN
 const GAS_FOR_NFT_APPROVE: Gas = 10_000_000_000_000;
 const GAS_FOR_MINT: Gas = 90_000_000_000_000;
 const NO_DEPOSIT: Balance = 0;
+const DEFAULT_PAGE_LIMIT: u64 = 50;
+/// Editions `nft_tokens_by_series_cursor` looks at per page, burned ones included
+const MAX_EDITIONS_SCANNED: u64 = 500;
 
 pub type TokenSeriesId = String;
 pub type MintBundleId = String;
@@ -76,6 +103,7 @@ trait NonFungibleTokenResolver {
         receiver_id: AccountId,
         token_id: TokenId,
         approved_account_ids: Option<HashMap<AccountId, u64>>,
+        approval_terms: Option<HashMap<AccountId, ApprovalTerms>>,
     ) -> bool;
 }
 
@@ -87,6 +115,84 @@ pub struct TokenSeries {
     price: Option<Balance>,
     is_mintable: bool,
     royalty: HashMap<AccountId, u32>,
+    royalty_locked: bool,
+    primary_split: HashMap<AccountId, u32>,
+    burned: u64,
+    sale_starts_at: Option<u64>,
+    sale_ends_at: Option<u64>,
+    title_format: TitleFormat,
+    transferability: Transferability,
+    validity: Option<SeriesValidity>,
+    metadata_frozen: bool,
+}
+
+/// How `nft_token` builds a title from the series title when the token has none of its own
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum TitleFormat {
+    /// "Title"
+    Plain,
+    /// "Title #2"
+    Edition,
+    /// "Title #2/10", same as `Edition` when the series has no max copies
+    EditionOfCopies,
+}
+
+/// Whether tokens of a series can leave the account they were minted to
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Transferability {
+    Transferable,
+    /// Can't be transferred or approved, only burned by the holder
+    Soulbound,
+    /// Soulbound, and the series creator can also revoke (burn) it
+    RevocableSoulbound,
+}
+
+/// What a token stores of its own. Everything else in its metadata comes from the series.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TokenData {
+    issued_at: Option<u64>,
+    overrides: Option<TokenMetadata>,
+}
+
+/// TokenSeries layout before the upgrade, read by `migrate_token_series`
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TokenSeriesV1 {
+    metadata: TokenMetadata,
+    creator_id: AccountId,
+    tokens: UnorderedSet<TokenId>,
+    price: Option<Balance>,
+    is_mintable: bool,
+    royalty: HashMap<AccountId, u32>,
+}
+
+/// A stored series in either layout. Each layout only decodes when it consumes every stored
+/// byte, so series already migrated are told apart from `TokenSeriesV1` ones.
+enum StoredTokenSeries {
+    V1(TokenSeriesV1),
+    Current(TokenSeries),
+}
+
+impl BorshDeserialize for StoredTokenSeries {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let bytes = std::mem::take(buf);
+        match TokenSeries::try_from_slice(bytes) {
+            Ok(token_series) => Ok(StoredTokenSeries::Current(token_series)),
+            Err(_) => TokenSeriesV1::try_from_slice(bytes).map(StoredTokenSeries::V1),
+        }
+    }
+}
+
+impl BorshSerialize for StoredTokenSeries {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        match self {
+            StoredTokenSeries::V1(token_series) => token_series.serialize(writer),
+            StoredTokenSeries::Current(token_series) => token_series.serialize(writer),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -96,6 +202,68 @@ pub struct TokenSeriesJson {
     metadata: TokenMetadata,
     creator_id: AccountId,
     royalty: HashMap<AccountId, u32>,
+    royalty_locked: bool,
+    primary_split: HashMap<AccountId, u32>,
+    price: Option<U128>,
+    is_mintable: bool,
+    minted: U64,
+    burned: U64,
+    /// None when the series has no max copies
+    remaining: Option<U64>,
+    sale_starts_at: Option<U64>,
+    sale_ends_at: Option<U64>,
+    title_format: TitleFormat,
+    transferability: Transferability,
+    validity: Option<SeriesValidity>,
+    metadata_frozen: bool,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SeriesCreatorProposal {
+    new_creator_id: AccountId,
+    migrate_royalty: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesCreatorProposalJson {
+    token_series_id: TokenSeriesId,
+    new_creator_id: AccountId,
+    migrate_royalty: bool,
+}
+
+/// NEP-171 `Token` plus the contract-specific fields
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenJson {
+    token_id: TokenId,
+    owner_id: AccountId,
+    metadata: Option<TokenMetadata>,
+    approved_account_ids: Option<HashMap<AccountId, u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    traits: Option<Traits>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnerCountJson {
+    account_id: AccountId,
+    count: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokensPage {
+    tokens: Vec<TokenJson>,
+    /// Pass back as `cursor` to fetch the next page, None once the end is reached
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenSeriesPage {
+    series: Vec<TokenSeriesJson>,
+    next_cursor: Option<TokenSeriesId>,
 }
 
 #[derive(BorshSerialize, BorshDeserialize)]
@@ -124,6 +292,7 @@ pub struct ContractV1 {
     // CUSTOM
     token_series_by_id: UnorderedMap<TokenSeriesId, TokenSeries>,
     treasury_id: AccountId,
+    mint_bundles: UnorderedMap<MintBundleId, MintBundle>,
 }
 
 #[near_bindgen]
@@ -135,6 +304,51 @@ pub struct Contract {
     token_series_by_id: UnorderedMap<TokenSeriesId, TokenSeries>,
     treasury_id: AccountId,
     mint_bundles: UnorderedMap<MintBundleId, MintBundle>,
+    token_royalty_by_id: LookupMap<TokenId, HashMap<AccountId, u32>>,
+    series_creator_proposals: LookupMap<TokenSeriesId, SeriesCreatorProposal>,
+    series_by_creator: LookupMap<AccountId, UnorderedSet<TokenSeriesId>>,
+    owners_by_series: LookupMap<TokenSeriesId, UnorderedMap<AccountId, u64>>,
+    /// Keyed by creator and snapshot id
+    holder_snapshots: UnorderedMap<(AccountId, SnapshotId), HolderSnapshot>,
+    open_snapshot_by_series: LookupMap<TokenSeriesId, (AccountId, SnapshotId)>,
+    open_contract_snapshot: Option<(AccountId, SnapshotId)>,
+    token_traits_by_id: LookupMap<TokenId, Traits>,
+    series_traits: LookupMap<TokenSeriesId, Traits>,
+    tokens_by_trait: LookupMap<Vec<u8>, UnorderedSet<TokenId>>,
+    trait_values_by_series: LookupMap<TokenSeriesId, UnorderedSet<(String, String)>>,
+    token_data_by_id: LookupMap<TokenId, TokenData>,
+    token_user_by_id: LookupMap<TokenId, TokenUser>,
+    tokens_per_user: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    recipes: UnorderedMap<RecipeId, Recipe>,
+    parent_by_id: LookupMap<TokenId, TokenId>,
+    children_by_id: LookupMap<TokenId, UnorderedSet<TokenId>>,
+    /// Levels of tokens attached below, only for tokens with children
+    subtree_height_by_id: LookupMap<TokenId, u32>,
+    /// Keyed by share contract
+    vaults: LookupMap<AccountId, Vault>,
+    vault_by_token: LookupMap<TokenId, AccountId>,
+    vault_nonce: u64,
+    fraction_share_code: LazyOption<Vec<u8>>,
+    staking_rate_by_series: LookupMap<TokenSeriesId, u128>,
+    stakes_by_id: LookupMap<TokenId, Stake>,
+    staked_tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    points_by_account: LookupMap<AccountId, u128>,
+    proposals: UnorderedMap<ProposalId, Proposal>,
+    proposal_threshold: Option<u64>,
+    operators_by_owner: LookupMap<AccountId, UnorderedMap<AccountId, Option<TokenSeriesId>>>,
+    approval_terms_by_id: LookupMap<TokenId, HashMap<AccountId, ApprovalTerms>>,
+    trusted_operators: UnorderedSet<AccountId>,
+    /// Opt-ins remember the operator version they were made under, re-adding an operator voids them
+    trusted_operator_opt_ins: LookupMap<AccountId, UnorderedMap<AccountId, (Option<TokenSeriesId>, u64)>>,
+    trusted_operator_versions: LookupMap<AccountId, u64>,
+    series_metadata_history: LookupMap<TokenSeriesId, Vec<SeriesMetadataVersion>>,
+    /// Ordered by token id, for cursors that survive tokens leaving the owner
+    token_ids_by_owner: LookupMap<AccountId, TreeMap<TokenId, ()>>,
+    /// Set by `migrate` until `migrate_owners_by_series` finishes. Meanwhile tokens not indexed yet
+    /// are indexed on their first owner change.
+    owners_backfill_pending: bool,
+    /// Set by `migrate` until `migrate_token_series` has rewritten every series
+    series_migration_pending: bool,
 }
 
 const DATA_IMAGE_SVG_COMIC_ICON: &str = "data:image/svg+xml;base64,PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciIHdpZHRoPSI2MyIgdmlld0JveD0iMCAwIDQ3LjI1IDQ3LjI1IiBoZWlnaHQ9IjYzIiB2ZXJzaW9uPSIxLjAiPjxkZWZzPjxjbGlwUGF0aCBpZD0iYSI+PHBhdGggZD0iTTEuMTcyIDBoNDYuNTEydjQ2LjM3OUgxLjE3MlptMCAwIi8+PC9jbGlwUGF0aD48L2RlZnM+PGcgY2xpcC1wYXRoPSJ1cmwoI2EpIj48cGF0aCBkPSJNMjQuNDI2LjEyMWMtMTIuNzAzIDAtMjMgMTAuMy0yMyAyMy4wMDQgMCAxMi43MDMgMTAuMjk3IDIzLjAwNCAyMyAyMy4wMDQgMTIuNzA3IDAgMjMuMDA0LTEwLjMgMjMuMDA0LTIzLjAwNEM0Ny40MyAxMC40MjIgMzcuMTMzLjEyMSAyNC40MjYuMTIxIi8+PC9nPjxwYXRoIGZpbGw9IiNGRkYiIGQ9Ik0yNC40MjIgMS40MTRjLTExLjk4OCAwLTIxLjcwNyA5LjcxOS0yMS43MDcgMjEuNzAzIDAgMTEuOTg4IDkuNzE5IDIxLjcwNyAyMS43MDcgMjEuNzA3IDExLjk4OCAwIDIxLjcwNy05LjcxOSAyMS43MDctMjEuNzA3IDAtMTEuOTg0LTkuNzE5LTIxLjcwMy0yMS43MDctMjEuNzAzIi8+PHBhdGggZD0iTTI0LjQxOCAyLjYwNWMtMTEuMzI4IDAtMjAuNTEyIDkuMTg0LTIwLjUxMiAyMC41MDggMCAxMS4zMzIgOS4xODQgMjAuNTEyIDIwLjUxMiAyMC41MTIgMTEuMzI4IDAgMjAuNTEyLTkuMTggMjAuNTEyLTIwLjUxMiAwLTExLjMyNC05LjE4NC0yMC41MDgtMjAuNTEyLTIwLjUwOCIvPjxwYXRoIGZpbGw9IiNGRkYiIGQ9Ik0xMC42MDIgMjQuNjRoLTIuNjNjLS4xMDkgMC0uMTk5LS4wODUtLjE5OS0uMTk1di0yLjYzM2EuMi4yIDAgMCAxIC4yLS4xOTVoMi42MjljLjEwOSAwIC4xOTkuMDkuMTk5LjE5NXYyLjYzM2MwIC4xMS0uMDkuMTk2LS4yLjE5Nk0xOS4xNiAyNS4zODdoLTMuOTNhLjI5NS4yOTUgMCAwIDEtLjI5My0uMjkzdi0zLjkzYzAtLjE2NC4xMzMtLjI5My4yOTMtLjI5M2gzLjkzYy4xNiAwIC4yOTMuMTI5LjI5My4yOTN2My45M2MwIC4xNi0uMTMzLjI5My0uMjkzLjI5M00yOS4xMjkgMjYuMDgyaC01LjE1MmEuMzguMzggMCAwIDEtLjM4My0uMzgzdi01LjE1MmMwLS4yMTEuMTcyLS4zODMuMzgzLS4zODNoNS4xNTJjLjIxIDAgLjM4My4xNzIuMzgzLjM4M3Y1LjE1MmEuMzguMzggMCAwIDEtLjM4My4zODNNNDAuNTkgMjYuODI4aC02LjQ1YS40ODYuNDg2IDAgMCAxLS40OC0uNDg0di02LjQ1YzAtLjI2MS4yMTktLjQ4LjQ4LS40OGg2LjQ1Yy4yNjUgMCAuNDguMjE5LjQ4LjQ4djYuNDVhLjQ4My40ODMgMCAwIDEtLjQ4LjQ4NCIvPjwvc3ZnPg==";
@@ -153,6 +367,52 @@ enum StorageKey {
     MintBundles,
     BoughtAccountId { mint_bundle_id: MintBundleId },
     MintBundleTokens { mint_bundle_id: MintBundleId },
+    TokenRoyaltyById,
+    SeriesCreatorProposals,
+    SeriesByCreator,
+    SeriesByCreatorInner { account_hash: Vec<u8> },
+    OwnersBySeries,
+    OwnersBySeriesInner { token_series: String },
+    HolderSnapshots,
+    HolderSnapshotInner { snapshot_hash: Vec<u8> },
+    TokenTraitsById,
+    SeriesTraits,
+    TokensByTrait,
+    TokensByTraitInner { trait_hash: Vec<u8> },
+    TraitValuesBySeries,
+    TokenDataById,
+    TokenUserById,
+    TokensPerUser,
+    TokensPerUserInner { account_hash: Vec<u8> },
+    Recipes,
+    RecipeRedeemedByAccount { recipe_id: RecipeId },
+    ParentById,
+    ChildrenById,
+    ChildrenByIdInner { token_hash: Vec<u8> },
+    Vaults,
+    VaultByToken,
+    StakingRateBySeries,
+    StakesById,
+    StakedTokensPerOwner,
+    StakedTokensPerOwnerInner { account_hash: Vec<u8> },
+    PointsByAccount,
+    Proposals,
+    ProposalVotes { proposal_id: ProposalId },
+    OperatorsByOwner,
+    OperatorsByOwnerInner { account_hash: Vec<u8> },
+    ApprovalTermsById,
+    TrustedOperators,
+    TrustedOperatorOptIns,
+    TrustedOperatorOptInsInner { account_hash: Vec<u8> },
+    SeriesMetadataHistory,
+    TokenIdsByOwner,
+    TraitValuesBySeriesInner { token_series: String },
+    FractionShareCode,
+    TrustedOperatorVersions,
+    TokenIdsByOwnerInner { account_hash: Vec<u8> },
+    HolderSnapshotSettled { snapshot_hash: Vec<u8> },
+    OpenSnapshotBySeries,
+    SubtreeHeightById,
 }
 
 #[near_bindgen]
@@ -194,9 +454,49 @@ impl Contract {
             metadata: LazyOption::new(StorageKey::Metadata, Some(&metadata)),
             treasury_id: treasury_id.to_string(),
             mint_bundles: UnorderedMap::new(StorageKey::MintBundles),
+            token_royalty_by_id: LookupMap::new(StorageKey::TokenRoyaltyById),
+            series_creator_proposals: LookupMap::new(StorageKey::SeriesCreatorProposals),
+            series_by_creator: LookupMap::new(StorageKey::SeriesByCreator),
+            owners_by_series: LookupMap::new(StorageKey::OwnersBySeries),
+            holder_snapshots: UnorderedMap::new(StorageKey::HolderSnapshots),
+            open_snapshot_by_series: LookupMap::new(StorageKey::OpenSnapshotBySeries),
+            open_contract_snapshot: None,
+            token_traits_by_id: LookupMap::new(StorageKey::TokenTraitsById),
+            series_traits: LookupMap::new(StorageKey::SeriesTraits),
+            tokens_by_trait: LookupMap::new(StorageKey::TokensByTrait),
+            trait_values_by_series: LookupMap::new(StorageKey::TraitValuesBySeries),
+            token_data_by_id: LookupMap::new(StorageKey::TokenDataById),
+            token_user_by_id: LookupMap::new(StorageKey::TokenUserById),
+            tokens_per_user: LookupMap::new(StorageKey::TokensPerUser),
+            recipes: UnorderedMap::new(StorageKey::Recipes),
+            parent_by_id: LookupMap::new(StorageKey::ParentById),
+            children_by_id: LookupMap::new(StorageKey::ChildrenById),
+            subtree_height_by_id: LookupMap::new(StorageKey::SubtreeHeightById),
+            vaults: LookupMap::new(StorageKey::Vaults),
+            vault_by_token: LookupMap::new(StorageKey::VaultByToken),
+            vault_nonce: 0,
+            fraction_share_code: LazyOption::new(StorageKey::FractionShareCode, None),
+            staking_rate_by_series: LookupMap::new(StorageKey::StakingRateBySeries),
+            stakes_by_id: LookupMap::new(StorageKey::StakesById),
+            staked_tokens_per_owner: LookupMap::new(StorageKey::StakedTokensPerOwner),
+            points_by_account: LookupMap::new(StorageKey::PointsByAccount),
+            proposals: UnorderedMap::new(StorageKey::Proposals),
+            proposal_threshold: None,
+            operators_by_owner: LookupMap::new(StorageKey::OperatorsByOwner),
+            approval_terms_by_id: LookupMap::new(StorageKey::ApprovalTermsById),
+            trusted_operators: UnorderedSet::new(StorageKey::TrustedOperators),
+            trusted_operator_opt_ins: LookupMap::new(StorageKey::TrustedOperatorOptIns),
+            trusted_operator_versions: LookupMap::new(StorageKey::TrustedOperatorVersions),
+            series_metadata_history: LookupMap::new(StorageKey::SeriesMetadataHistory),
+            token_ids_by_owner: LookupMap::new(StorageKey::TokenIdsByOwner),
+            owners_backfill_pending: false,
+            series_migration_pending: false,
         }
     }
 
+    /// Upgrades from `ContractV1`. Run the backfills after it in this order:
+    /// `migrate_token_series` until it reaches the series count (series in the old layout can't be read before),
+    /// then `migrate_series_by_creator`, `migrate_owners_by_series` and `migrate_token_metadata`.
     #[init(ignore_state)]
     pub fn migrate() -> Self {
         let prev: ContractV1 = env::state_read().expect("ERR_NOT_INITIALIZED");
@@ -211,12 +511,178 @@ impl Contract {
             metadata: prev.metadata,
             token_series_by_id: prev.token_series_by_id,
             treasury_id: prev.treasury_id,
-            mint_bundles: UnorderedMap::new(StorageKey::MintBundles),
+            mint_bundles: prev.mint_bundles,
+            token_royalty_by_id: LookupMap::new(StorageKey::TokenRoyaltyById),
+            series_creator_proposals: LookupMap::new(StorageKey::SeriesCreatorProposals),
+            series_by_creator: LookupMap::new(StorageKey::SeriesByCreator),
+            owners_by_series: LookupMap::new(StorageKey::OwnersBySeries),
+            holder_snapshots: UnorderedMap::new(StorageKey::HolderSnapshots),
+            open_snapshot_by_series: LookupMap::new(StorageKey::OpenSnapshotBySeries),
+            open_contract_snapshot: None,
+            token_traits_by_id: LookupMap::new(StorageKey::TokenTraitsById),
+            series_traits: LookupMap::new(StorageKey::SeriesTraits),
+            tokens_by_trait: LookupMap::new(StorageKey::TokensByTrait),
+            trait_values_by_series: LookupMap::new(StorageKey::TraitValuesBySeries),
+            token_data_by_id: LookupMap::new(StorageKey::TokenDataById),
+            token_user_by_id: LookupMap::new(StorageKey::TokenUserById),
+            tokens_per_user: LookupMap::new(StorageKey::TokensPerUser),
+            recipes: UnorderedMap::new(StorageKey::Recipes),
+            parent_by_id: LookupMap::new(StorageKey::ParentById),
+            children_by_id: LookupMap::new(StorageKey::ChildrenById),
+            subtree_height_by_id: LookupMap::new(StorageKey::SubtreeHeightById),
+            vaults: LookupMap::new(StorageKey::Vaults),
+            vault_by_token: LookupMap::new(StorageKey::VaultByToken),
+            vault_nonce: 0,
+            fraction_share_code: LazyOption::new(StorageKey::FractionShareCode, None),
+            staking_rate_by_series: LookupMap::new(StorageKey::StakingRateBySeries),
+            stakes_by_id: LookupMap::new(StorageKey::StakesById),
+            staked_tokens_per_owner: LookupMap::new(StorageKey::StakedTokensPerOwner),
+            points_by_account: LookupMap::new(StorageKey::PointsByAccount),
+            proposals: UnorderedMap::new(StorageKey::Proposals),
+            proposal_threshold: None,
+            operators_by_owner: LookupMap::new(StorageKey::OperatorsByOwner),
+            approval_terms_by_id: LookupMap::new(StorageKey::ApprovalTermsById),
+            trusted_operators: UnorderedSet::new(StorageKey::TrustedOperators),
+            trusted_operator_opt_ins: LookupMap::new(StorageKey::TrustedOperatorOptIns),
+            trusted_operator_versions: LookupMap::new(StorageKey::TrustedOperatorVersions),
+            series_metadata_history: LookupMap::new(StorageKey::SeriesMetadataHistory),
+            token_ids_by_owner: LookupMap::new(StorageKey::TokenIdsByOwner),
+            owners_backfill_pending: true,
+            series_migration_pending: true,
         };
 
         this
     }
 
+    /// Rewrites token series stored in the `TokenSeriesV1` layout, `limit` series at a time
+    /// starting from `from_index`. Returns the index to continue from.
+    /// Series already in the current layout are skipped, so a chunk can safely be run again.
+    pub fn migrate_token_series(&mut self, from_index: U64, limit: U64) -> U64 {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Only owner"
+        );
+
+        let stored_token_series_by_id: UnorderedMap<TokenSeriesId, StoredTokenSeries> =
+            UnorderedMap::new(StorageKey::TokenSeriesById);
+        let keys = self.token_series_by_id.keys_as_vector();
+        let end_index = std::cmp::min(from_index.0.saturating_add(limit.0), keys.len());
+
+        for index in from_index.0..end_index {
+            let token_series_id = keys.get(index).unwrap();
+            let prev = match stored_token_series_by_id.get(&token_series_id).unwrap() {
+                StoredTokenSeries::V1(prev) => prev,
+                StoredTokenSeries::Current(_) => continue,
+            };
+            self.token_series_by_id.insert(&token_series_id, &TokenSeries {
+                metadata: prev.metadata,
+                creator_id: prev.creator_id,
+                tokens: prev.tokens,
+                price: prev.price,
+                is_mintable: prev.is_mintable,
+                royalty: prev.royalty,
+                royalty_locked: false,
+                primary_split: HashMap::new(),
+                burned: 0,
+                sale_starts_at: None,
+                sale_ends_at: None,
+                title_format: TitleFormat::Edition,
+                transferability: Transferability::Transferable,
+                validity: None,
+                metadata_frozen: false,
+            });
+        }
+
+        if end_index == keys.len() {
+            self.series_migration_pending = false;
+        }
+
+        U64::from(end_index)
+    }
+
+    /// Backfills `series_by_creator` for series created before the index existed,
+    /// `limit` series at a time starting from `from_index`. Returns the index to continue from.
+    pub fn migrate_series_by_creator(&mut self, from_index: U64, limit: U64) -> U64 {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Only owner"
+        );
+        assert!(!self.series_migration_pending, "Marble: run migrate_token_series first");
+
+        let keys = self.token_series_by_id.keys_as_vector();
+        let end_index = std::cmp::min(from_index.0 + limit.0, keys.len());
+
+        for index in from_index.0..end_index {
+            let token_series_id = keys.get(index).unwrap();
+            let token_series = self.token_series_by_id.get(&token_series_id).unwrap();
+            self.internal_add_series_to_creator(&token_series.creator_id, &token_series_id);
+        }
+
+        U64::from(end_index)
+    }
+
+    /// Backfills `owners_by_series` and `token_ids_by_owner` for tokens minted before the indexes existed, walking tokens
+    /// in id order from `cursor`. Returns the cursor to continue from, None when done.
+    /// Owner changes stay open meanwhile, they index the tokens they touch.
+    /// Tokens already indexed are skipped, so a chunk can safely be run again.
+    pub fn migrate_owners_by_series(&mut self, cursor: Option<TokenId>, limit: U64) -> Option<TokenId> {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Only owner"
+        );
+        assert!(limit.0 > 0, "Marble: limit must be greater than 0");
+
+        let tokens: Vec<(TokenId, AccountId)> = match cursor {
+            Some(cursor) => self.tokens.owner_by_id.range((Bound::Excluded(cursor), Bound::Unbounded)).take(limit.0 as usize).collect(),
+            None => self.tokens.owner_by_id.iter().take(limit.0 as usize).collect(),
+        };
+
+        for (token_id, owner_id) in tokens.iter() {
+            if !self.is_owner_indexed(owner_id, token_id) {
+                self.internal_index_owner_change(token_id, None, Some(owner_id));
+            }
+        }
+
+        if (tokens.len() as u64) < limit.0 {
+            self.owners_backfill_pending = false;
+            None
+        } else {
+            tokens.last().map(|(token_id, _)| token_id.clone())
+        }
+    }
+
+    /// Moves tokens minted with a full `TokenMetadata` record to a compact `TokenData`,
+    /// walking tokens in id order from `cursor`. Returns the cursor to continue from, None when done.
+    pub fn migrate_token_metadata(&mut self, cursor: Option<TokenId>, limit: U64) -> Option<TokenId> {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Only owner"
+        );
+
+        let token_ids: Vec<TokenId> = match cursor {
+            Some(cursor) => self.tokens.owner_by_id.range((Bound::Excluded(cursor), Bound::Unbounded)).take(limit.0 as usize).map(|(token_id, _)| token_id).collect(),
+            None => self.tokens.owner_by_id.iter().take(limit.0 as usize).map(|(token_id, _)| token_id).collect(),
+        };
+
+        if let Some(token_metadata_by_id) = &mut self.tokens.token_metadata_by_id {
+            for token_id in token_ids.iter() {
+                if let Some(token_metadata) = token_metadata_by_id.remove(token_id) {
+                    self.token_data_by_id.insert(token_id, &compact_token_metadata(token_metadata));
+                }
+            }
+        }
+
+        if token_ids.len() as u64 == limit.0 {
+            token_ids.last().cloned()
+        } else {
+            None
+        }
+    }
+
     // Treasury
     #[payable]
     pub fn set_treasury(&mut self, treasury_id: ValidAccountId) {
@@ -238,6 +704,8 @@ impl Contract {
         price: Option<U128>,
         royalty: Option<HashMap<AccountId, u32>>,
         creator_id: ValidAccountId,
+        primary_split: Option<HashMap<AccountId, u32>>,
+        transferability: Option<Transferability>,
     ) -> TokenSeriesJson {
         let initial_storage_usage = env::storage_usage();
 
@@ -254,27 +722,11 @@ impl Contract {
         assert!(title.is_some(), "Marble: token_metadata.title is required");
 
 
-        let mut total_perpetual = 0;
-        let mut total_accounts = 0;
-        let royalty_res: HashMap<AccountId, u32> = if let Some(royalty) = royalty {
-            for (k, v) in royalty.iter() {
-                if !is_valid_account_id(k.as_bytes()) {
-                    env::panic("Not valid account_id for royalty".as_bytes());
-                };
-                total_perpetual += *v;
-                total_accounts += 1;
-            }
-            royalty
-        } else {
-            HashMap::new()
-        };
-
-        assert!(total_accounts <= 10, "Marble: royalty exceeds 10 accounts");
+        let royalty_res: HashMap<AccountId, u32> = royalty.unwrap_or_else(HashMap::new);
+        assert_valid_royalty(&royalty_res);
 
-        assert!(
-            total_perpetual <= 9000,
-            "Marble Exceeds maximum royalty -> 9000",
-        );
+        let primary_split_res: HashMap<AccountId, u32> = primary_split.unwrap_or_else(HashMap::new);
+        assert_valid_primary_split(&primary_split_res);
 
         let price_res: Option<u128> = if price.is_some() {
             Some(price.unwrap().0)
@@ -295,7 +747,17 @@ impl Contract {
             price: price_res,
             is_mintable: true,
             royalty: royalty_res.clone(),
+            royalty_locked: false,
+            primary_split: primary_split_res.clone(),
+            burned: 0,
+            sale_starts_at: None,
+            sale_ends_at: None,
+            title_format: TitleFormat::Edition,
+            transferability: transferability.unwrap_or(Transferability::Transferable),
+            validity: None,
+            metadata_frozen: false,
         });
+        self.internal_add_series_to_creator(&creator_id.to_string(), &token_series_id);
 
         env::log(
             json!({
@@ -305,7 +767,9 @@ impl Contract {
                     "token_metadata": token_metadata,
                     "creator_id": creator_id,
                     "price": price,
-                    "royalty": royalty_res
+                    "royalty": royalty_res,
+                    "primary_split": primary_split_res,
+                    "transferability": transferability,
                 }
             })
                 .to_string()
@@ -314,12 +778,7 @@ impl Contract {
 
         refund_deposit(env::storage_usage() - initial_storage_usage, 0);
 
-        TokenSeriesJson {
-            token_series_id,
-            metadata: token_metadata,
-            creator_id: creator_id.into(),
-            royalty: royalty_res,
-        }
+        token_series_to_json(token_series_id.clone(), self.token_series_by_id.get(&token_series_id).unwrap())
     }
 
     #[payable]
@@ -333,25 +792,24 @@ impl Contract {
 
         let token_series = self.token_series_by_id.get(&token_series_id).expect("Marble: Token series not exist");
         let price: u128 = token_series.price.expect("Marble: not for sale");
+        assert_sale_open(&token_series);
         let attached_deposit = env::attached_deposit();
         assert!(
             attached_deposit >= price,
             "Marble: attached deposit is less than price : {}",
             price
         );
-        let token_id: TokenId = self._nft_mint_series(token_series_id, receiver_id.to_string(), nft_metadata);
+        let nft_metadata = nft_metadata.map(|metadata| keep_creator_fields(metadata, None));
+        let token_id: TokenId = self._nft_mint_series(token_series_id, receiver_id.to_string(), nft_metadata, None);
 
-        let for_treasury = price as u128 * TREASURY_FEE / 10_000u128;
-        let price_deducted = price - for_treasury;
-        Promise::new(token_series.creator_id).transfer(price_deducted);
-        Promise::new(self.treasury_id.clone()).transfer(for_treasury);
+        let primary_split = self.internal_pay_primary_sale(&token_series, price);
 
         refund_deposit(env::storage_usage() - initial_storage_usage, price);
 
         NearEvent::log_nft_mint(
             receiver_id.to_string(),
             vec![token_id.clone()],
-            Some(json!({"price": price.to_string()}).to_string()),
+            Some(json!({"price": price.to_string(), "primary_split": primary_split}).to_string()),
         );
 
         token_id
@@ -363,12 +821,15 @@ impl Contract {
         token_series_id: TokenSeriesId,
         receiver_id: ValidAccountId,
         nft_metadata: Option<TokenMetadata>,
+        royalty: Option<HashMap<AccountId, u32>>,
+        traits: Option<Traits>,
     ) -> TokenId {
         let initial_storage_usage = env::storage_usage();
 
         let token_series = self.token_series_by_id.get(&token_series_id).expect("Marble: Token series not exist");
         assert_eq!(env::predecessor_account_id(), token_series.creator_id, "Marble: not creator");
-        let token_id: TokenId = self._nft_mint_series(token_series_id, receiver_id.to_string(),nft_metadata);
+        let token_id: TokenId = self._nft_mint_series(token_series_id, receiver_id.to_string(), nft_metadata, traits);
+        self.internal_set_token_royalty(&token_id, royalty);
 
         refund_deposit(env::storage_usage() - initial_storage_usage, 0);
 
@@ -388,32 +849,18 @@ impl Contract {
         account_id: ValidAccountId,
         nft_metadata:Option<TokenMetadata>,
         msg: Option<String>,
+        royalty: Option<HashMap<AccountId, u32>>,
+        traits: Option<Traits>,
+        expires_at: Option<U64>,
+        min_price: Option<U128>,
     ) -> Option<Promise> {
         let initial_storage_usage = env::storage_usage();
 
         let token_series = self.token_series_by_id.get(&token_series_id).expect("Marble: Token series not exist");
         assert_eq!(env::predecessor_account_id(), token_series.creator_id, "Marble: not creator");
-        let token_id: TokenId = self._nft_mint_series(token_series_id, token_series.creator_id.clone(),nft_metadata);
-
-        // Need to copy the nft_approve code here to solve the gas problem
-        // get contract-level LookupMap of token_id to approvals HashMap
-        let approvals_by_id = self.tokens.approvals_by_id.as_mut().unwrap();
-
-        // update HashMap of approvals for this token
-        let approved_account_ids =
-            &mut approvals_by_id.get(&token_id).unwrap_or_else(|| HashMap::new());
-        let account_id: AccountId = account_id.into();
-        let approval_id: u64 =
-            self.tokens.next_approval_id_by_id.as_ref().unwrap().get(&token_id).unwrap_or_else(|| 1u64);
-        approved_account_ids.insert(account_id.clone(), approval_id);
-
-        // save updated approvals HashMap to contract's LookupMap
-        approvals_by_id.insert(&token_id, &approved_account_ids);
-
-        // increment next_approval_id for this token
-        self.tokens.next_approval_id_by_id.as_mut().unwrap().insert(&token_id, &(approval_id + 1));
-
-        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+        assert_eq!(token_series.transferability, Transferability::Transferable, "Marble: token is soulbound");
+        let token_id: TokenId = self._nft_mint_series(token_series_id, token_series.creator_id.clone(), nft_metadata, traits);
+        self.internal_set_token_royalty(&token_id, royalty);
 
         NearEvent::log_nft_mint(
             token_series.creator_id.clone(),
@@ -421,18 +868,90 @@ impl Contract {
             None,
         );
 
-        if let Some(msg) = msg {
-            Some(ext_approval_receiver::nft_on_approve(
-                token_id,
-                token_series.creator_id,
-                approval_id,
-                msg,
-                &account_id,
-                NO_DEPOSIT,
-                env::prepaid_gas() - GAS_FOR_NFT_APPROVE - GAS_FOR_MINT,
-            ))
-        } else {
-            None
+        let promise = self.internal_approve(
+            &token_id,
+            &token_series.creator_id,
+            vec![account_id.into()],
+            msg,
+            expires_at.map(|expires_at| expires_at.0),
+            min_price.map(|min_price| min_price.0),
+            GAS_FOR_NFT_APPROVE + GAS_FOR_MINT,
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+
+        promise
+    }
+
+    fn internal_add_series_to_creator(&mut self, creator_id: &AccountId, token_series_id: &TokenSeriesId) {
+        let mut series_ids = self.series_by_creator.get(creator_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::SeriesByCreatorInner {
+                account_hash: env::sha256(creator_id.as_bytes()),
+            })
+        });
+        series_ids.insert(token_series_id);
+        self.series_by_creator.insert(creator_id, &series_ids);
+    }
+
+    fn internal_remove_series_from_creator(&mut self, creator_id: &AccountId, token_series_id: &TokenSeriesId) {
+        if let Some(mut series_ids) = self.series_by_creator.get(creator_id) {
+            series_ids.remove(token_series_id);
+            if series_ids.is_empty() {
+                self.series_by_creator.remove(creator_id);
+            } else {
+                self.series_by_creator.insert(creator_id, &series_ids);
+            }
+        }
+    }
+
+    /// Sends `price` minus the treasury fee to the series creator, or across the series
+    /// `primary_split` when one is set. Returns what each account received, treasury excluded.
+    fn internal_pay_primary_sale(&self, token_series: &TokenSeries, price: Balance) -> HashMap<AccountId, U128> {
+        let for_treasury = price as u128 * TREASURY_FEE / 10_000u128;
+        let price_deducted = price - for_treasury;
+
+        let payout = primary_sale_payout(token_series, price_deducted);
+        for (account_id, amount) in payout.iter() {
+            if amount.0 > 0 {
+                Promise::new(account_id.clone()).transfer(amount.0);
+            }
+        }
+        Promise::new(self.treasury_id.clone()).transfer(for_treasury);
+
+        payout
+    }
+
+    fn internal_set_token_royalty(&mut self, token_id: &TokenId, royalty: Option<HashMap<AccountId, u32>>) {
+        if let Some(royalty) = royalty {
+            assert_valid_royalty(&royalty);
+            self.token_royalty_by_id.insert(token_id, &royalty);
+        }
+    }
+
+    /// Per-token royalty override if one was set at mint, otherwise the series royalty
+    fn internal_get_royalty(&self, token_id: &TokenId) -> HashMap<AccountId, u32> {
+        if let Some(royalty) = self.token_royalty_by_id.get(token_id) {
+            return royalty;
+        }
+        self.token_series_by_id.get(&get_token_series_id(token_id)).expect("no type").royalty
+    }
+
+    /// The token's own metadata fields, from its `TokenData` or the record of a not yet migrated token
+    fn internal_get_token_metadata(&self, token_id: &TokenId) -> TokenMetadata {
+        match self.token_data_by_id.get(token_id) {
+            Some(token_data) => {
+                let mut token_metadata = token_data.overrides.unwrap_or_else(empty_token_metadata);
+                if token_metadata.issued_at.is_none() {
+                    token_metadata.issued_at = token_data.issued_at.map(|issued_at| issued_at.to_string());
+                }
+                token_metadata
+            }
+            None => self
+                .tokens
+                .token_metadata_by_id
+                .as_ref()
+                .and_then(|by_id| by_id.get(token_id))
+                .unwrap_or_else(empty_token_metadata),
         }
     }
 
@@ -441,6 +960,7 @@ impl Contract {
         token_series_id: TokenSeriesId,
         receiver_id: AccountId,
         nft_metadata: Option<TokenMetadata>,
+        traits: Option<Traits>,
     ) -> TokenId {
         let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Marble: Token series not exist");
         assert!(
@@ -460,30 +980,30 @@ impl Contract {
         token_series.tokens.insert(&token_id);
         self.token_series_by_id.insert(&token_series_id, &token_series);
 
-        // you can add custom metadata to each token here
-
-        let _metadata: TokenMetadata = match nft_metadata {
-            None => {
-                TokenMetadata {
-                    title: None,          // ex. "Arch Nemesis: Mail Carrier" or "Parcel #5055"
-                    description: None,    // free-form description
-                    media: None, // URL to associated media, preferably to decentralized, content-addressed storage
-                    media_hash: None, // Base64-encoded sha256 hash of content referenced by the `media` field. Required if `media` is included.
-                    copies: None, // number of copies of this set of metadata in existence when token was minted.
-                    issued_at: Some(env::block_timestamp().to_string()), // ISO 8601 datetime when token was issued or minted
-                    expires_at: None, // ISO 8601 datetime when token expires
-                    starts_at: None, // ISO 8601 datetime when token starts being valid
-                    updated_at: None, // ISO 8601 datetime when token was last updated
-                    extra: None, // anything extra the NFT wants to store on-chain. Can be stringified JSON.
-                    reference: None, // URL to an off-chain JSON file with more info.
-                    reference_hash: None, // Base64-encoded sha256 hash of JSON from reference field. Required if `reference` is included.
-                }
-            },
-            Some(metadata) => {
-                metadata
-            },
+        let nft_metadata = match &token_series.validity {
+            Some(validity) => Some(stamp_validity(
+                nft_metadata.unwrap_or_else(empty_token_metadata),
+                &validity.window,
+                env::block_timestamp(),
+            )),
+            None => nft_metadata,
         };
 
+        // only the token's own fields are stored, the rest is read from the series in nft_token
+        #[cfg(not(feature = "legacy-token-metadata"))]
+        self.token_data_by_id.insert(&token_id, &TokenData {
+            issued_at: Some(env::block_timestamp()),
+            overrides: nft_metadata,
+        });
+
+        // per-token layout from before `TokenData`, only built to measure it in the simulation tests
+        #[cfg(feature = "legacy-token-metadata")]
+        {
+            let mut token_metadata = nft_metadata.unwrap_or_else(empty_token_metadata);
+            token_metadata.issued_at = Some(env::block_timestamp().to_string());
+            self.tokens.token_metadata_by_id.as_mut().and_then(|by_id| by_id.insert(&token_id, &token_metadata));
+        }
+
         //let token = self.tokens.mint(token_id, receiver_id, metadata);
         // From : https://github.com/near/near-sdk-rs/blob/master/near-contract-standards/src/non_fungible_token/core/core_impl.rs#L359
         // This allows lazy minting
@@ -491,11 +1011,6 @@ impl Contract {
         let owner_id: AccountId = receiver_id;
         self.tokens.owner_by_id.insert(&token_id, &owner_id);
 
-        self.tokens
-            .token_metadata_by_id
-            .as_mut()
-            .and_then(|by_id| by_id.insert(&token_id, &_metadata));
-
         if let Some(tokens_per_owner) = &mut self.tokens.tokens_per_owner {
             let mut token_ids = tokens_per_owner.get(&owner_id).unwrap_or_else(|| {
                 UnorderedSet::new(StorageKey::TokensPerOwner {
@@ -506,6 +1021,9 @@ impl Contract {
             tokens_per_owner.insert(&owner_id, &token_ids);
         }
 
+        self.internal_on_owner_change(&token_id, None, Some(&owner_id));
+        self.internal_set_token_traits(&token_series_id, &token_id, traits);
+
 
         token_id
     }
@@ -547,13 +1065,15 @@ impl Contract {
         );
     }
 
+    /// Recorded in the series metadata history, the caller pays for the entry
     #[payable]
     pub fn nft_decrease_series_copies(
         &mut self,
         token_series_id: TokenSeriesId,
         decrease_copies: U64,
     ) -> U64 {
-        assert_one_yocto();
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
 
         let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
         assert_eq!(
@@ -561,6 +1081,7 @@ impl Contract {
             token_series.creator_id,
             "Marble: Creator only"
         );
+        assert!(!token_series.metadata_frozen, "Marble: series metadata is frozen");
 
         let minted_copies = token_series.tokens.len();
         let copies = token_series.metadata.copies.unwrap();
@@ -577,6 +1098,7 @@ impl Contract {
             false
         };
 
+        self.internal_push_series_metadata_history(&token_series_id, &token_series.metadata, vec!["copies".to_string()]);
         token_series.metadata.copies = Some(copies - decrease_copies.0);
 
         self.token_series_by_id.insert(&token_series_id, &token_series);
@@ -592,6 +1114,9 @@ impl Contract {
                 .to_string()
                 .as_bytes(),
         );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+
         U64::from(token_series.metadata.copies.unwrap())
     }
 
@@ -633,683 +1158,1649 @@ impl Contract {
         return price;
     }
 
+    /// Only before the first mint, so holders never have tokens locked or unlocked under them
     #[payable]
-    pub fn nft_change_metadata(&mut self, token_id: TokenId, metadata: TokenMetadata) {
+    pub fn nft_set_series_transferability(&mut self, token_series_id: TokenSeriesId, transferability: Transferability) {
         assert_one_yocto();
-        let owner_id = self.tokens.owner_by_id.get(&token_id).unwrap();
+
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
         assert_eq!(
-            owner_id,
             env::predecessor_account_id(),
-            "Token owner only"
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(token_series.tokens.is_empty(), "Marble: series already has minted tokens");
+
+        token_series.transferability = transferability;
+        self.token_series_by_id.insert(&token_series_id, &token_series);
+        env::log(
+            json!({
+                "type": "nft_set_series_transferability",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "transferability": transferability,
+                }
+            })
+                .to_string()
+                .as_bytes(),
         );
-        // let mut token_metadata = self.tokens.token_metadata_by_id.as_ref().unwrap().get(&token_id).unwrap();
-        let new_metadata = Some(metadata);
-        self.tokens
-            .token_metadata_by_id
-            .as_mut()
-            .and_then(|by_id| by_id.insert(&token_id, &new_metadata.as_ref().unwrap()));
-        
     }
 
     #[payable]
-    pub fn nft_burn(&mut self, token_id: TokenId) {
+    pub fn nft_set_series_title_format(&mut self, token_series_id: TokenSeriesId, title_format: TitleFormat) {
         assert_one_yocto();
 
-        let owner_id = self.tokens.owner_by_id.get(&token_id).unwrap();
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
         assert_eq!(
-            owner_id,
             env::predecessor_account_id(),
-            "Token owner only"
+            token_series.creator_id,
+            "Marble: Creator only"
         );
 
-        if let Some(next_approval_id_by_id) = &mut self.tokens.next_approval_id_by_id {
-            next_approval_id_by_id.remove(&token_id);
-        }
-
-        if let Some(approvals_by_id) = &mut self.tokens.approvals_by_id {
-            approvals_by_id.remove(&token_id);
-        }
-
-        if let Some(tokens_per_owner) = &mut self.tokens.tokens_per_owner {
-            let mut token_ids = tokens_per_owner.get(&owner_id).unwrap();
-            token_ids.remove(&token_id);
-            tokens_per_owner.insert(&owner_id, &token_ids);
-        }
-
-        if let Some(token_metadata_by_id) = &mut self.tokens.token_metadata_by_id {
-            token_metadata_by_id.remove(&token_id);
-        }
-
-        self.tokens.owner_by_id.remove(&token_id);
-
-        NearEvent::log_nft_burn(
-            owner_id,
-            vec![token_id],
-            None,
-            None,
+        token_series.title_format = title_format;
+        self.token_series_by_id.insert(&token_series_id, &token_series);
+        env::log(
+            json!({
+                "type": "nft_set_series_title_format",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "title_format": title_format,
+                }
+            })
+                .to_string()
+                .as_bytes(),
         );
     }
 
-    // Mint Bundles
-
+    /// Timestamps in nanoseconds; `nft_buy` only succeeds within `[sale_starts_at, sale_ends_at)`
     #[payable]
-    pub fn buy_mint_bundle(
+    pub fn nft_set_series_sale_window(
         &mut self,
-        mint_bundle_id: MintBundleId,
-        receiver_id: ValidAccountId,
-    ) -> Option<TokenId> {
-        let initial_storage_usage = env::storage_usage();
-        let mut mint_bundle = self.mint_bundles.get(&mint_bundle_id).expect(
-            "Marble: Mint bundle does not exist or already finished"
+        token_series_id: TokenSeriesId,
+        sale_starts_at: Option<U64>,
+        sale_ends_at: Option<U64>,
+    ) {
+        assert_one_yocto();
+
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
         );
 
-        assert_eq!(env::predecessor_account_id(), receiver_id.to_string(), "Marble: Can only buy for caller");
+        if let (Some(sale_starts_at), Some(sale_ends_at)) = (sale_starts_at, sale_ends_at) {
+            assert!(sale_starts_at.0 < sale_ends_at.0, "Marble: sale_starts_at must be before sale_ends_at");
+        }
 
-        let price = mint_bundle.price.expect("Marble: Mint bundle hasn't started yet");
-        assert!(
-            env::attached_deposit() > price,
-            "Marble: Attached deposit lower than mint price"
+        token_series.sale_starts_at = sale_starts_at.map(|v| v.0);
+        token_series.sale_ends_at = sale_ends_at.map(|v| v.0);
+        self.token_series_by_id.insert(&token_series_id, &token_series);
+        env::log(
+            json!({
+                "type": "nft_set_series_sale_window",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "sale_starts_at": sale_starts_at,
+                    "sale_ends_at": sale_ends_at,
+                }
+            })
+                .to_string()
+                .as_bytes(),
         );
+    }
 
-        if let Some(limit_buy) = mint_bundle.limit_buy {
-            let mint_count = mint_bundle.bought_account_ids.get(&receiver_id.to_string()).unwrap_or(0);
-            assert!(
-                mint_count < limit_buy,
-                "Marble: Mint exhausted for account_id {}",
-                receiver_id
-            );
+    #[payable]
+    pub fn nft_set_series_royalty(
+        &mut self,
+        token_series_id: TokenSeriesId,
+        royalty: HashMap<AccountId, u32>,
+    ) -> HashMap<AccountId, u32> {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
 
-            mint_bundle.bought_account_ids.insert(
-                &receiver_id.to_string(),
-                &(mint_count + 1)
-            );
-        }
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(!token_series.royalty_locked, "Marble: royalty is locked");
+        assert_valid_royalty(&royalty);
 
-        return if let Some(mut token_series_ids) = mint_bundle.token_series_ids {
-            let seed_num = get_random_number(0) as u64;
-            let index = seed_num % token_series_ids.len();
-            let token_series_id = token_series_ids.get(index).unwrap();
-            let token_id = self._nft_mint_series(token_series_id.clone(), receiver_id.to_string(),None);
+        let old_royalty = std::mem::replace(&mut token_series.royalty, royalty.clone());
+        self.token_series_by_id.insert(&token_series_id, &token_series);
 
-            let token_series = self.token_series_by_id.get(&token_series_id.to_string()).unwrap();
-            if !token_series.is_mintable {
-                token_series_ids.swap_remove(index);
-            }
+        env::log(
+            json!({
+                "type": "nft_set_series_royalty",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "old_royalty": old_royalty,
+                    "royalty": royalty,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
 
-            if token_series_ids.len() == 0 {
-                self.mint_bundles.remove(&mint_bundle_id);
-            } else {
-                mint_bundle.token_series_ids = Some(token_series_ids);
-                self.mint_bundles.insert(&mint_bundle_id, &mint_bundle);
-            }
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
 
-            if price > 0 {
-                let for_treasury = price as u128 * TREASURY_FEE / 10_000u128;
-                let price_deducted = price - for_treasury;
-                Promise::new(token_series.creator_id).transfer(price_deducted);
-                Promise::new(self.treasury_id.clone()).transfer(for_treasury);
-            }
+        royalty
+    }
 
-            refund_deposit(env::storage_usage() - initial_storage_usage, price);
+    /// One-way: once locked, `nft_set_series_royalty` is rejected for the series forever
+    #[payable]
+    pub fn nft_lock_series_royalty(&mut self, token_series_id: TokenSeriesId) {
+        assert_one_yocto();
 
-            NearEvent::log_nft_mint(
-                receiver_id.to_string(),
-                vec![token_id.clone()],
-                Some(json!({"price": price.to_string()}).to_string()),
-            );
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(!token_series.royalty_locked, "Marble: royalty is locked");
 
-            Some(token_id)
-        } else {
-            None
-        }
+        token_series.royalty_locked = true;
+        self.token_series_by_id.insert(&token_series_id, &token_series);
+        env::log(
+            json!({
+                "type": "nft_lock_series_royalty",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "royalty": token_series.royalty,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
     }
 
+    /// Only before the first mint, so co-creators can't be cut out of sales they agreed to
     #[payable]
-    pub fn create_mint_bundle(
+    pub fn nft_set_series_primary_split(
         &mut self,
-        mint_bundle_id: MintBundleId,
-        token_series_ids: Option<Vec<TokenSeriesId>>,
-        token_ids: Option<Vec<TokenId>>,
-        price: Option<U128>,
-        limit_buy: Option<u32>,
-    ) -> bool {
+        token_series_id: TokenSeriesId,
+        primary_split: HashMap<AccountId, u32>,
+    ) -> HashMap<AccountId, u32> {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
         let initial_storage_usage = env::storage_usage();
 
-        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Marble: Only owner");
-
-        let mint_bundle = self.mint_bundles.get(&mint_bundle_id);
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(
+            token_series.tokens.is_empty() && token_series.burned == 0,
+            "Marble: series already has minted tokens"
+        );
+        assert_valid_primary_split(&primary_split);
 
-        if mint_bundle.is_some() {
-            panic!("Mint bundle already exists");
-        }
+        token_series.primary_split = primary_split.clone();
+        self.token_series_by_id.insert(&token_series_id, &token_series);
 
-        if token_series_ids.is_some() {
-            assert!(
-                token_ids.is_none(),
-                "Must chose either token_series_ids or token_ids"
-            );
-            let mut token_series_ids_internal: Vector<TokenSeriesId> = Vector::new(
-                StorageKey::MintBundleTokens { mint_bundle_id: mint_bundle_id.clone() }
-            );
-            for token_series_id in token_series_ids.unwrap() {
-                token_series_ids_internal.push(&token_series_id);
-            }
-            self.mint_bundles.insert(&mint_bundle_id.clone(), &MintBundle {
-                token_series_ids: Some(token_series_ids_internal),
-                token_ids: None,
-                price: match price {
-                    Some(x) => Some(x.0),
-                    None => None
-                },
-                limit_buy,
-                bought_account_ids: LookupMap::new(StorageKey::BoughtAccountId { mint_bundle_id }),
-            });
-        } else if token_ids.is_some() {
-            assert!(
-                token_series_ids.is_none(),
-                "Must chose either token_series_ids or token_ids"
-            );
-            panic!("Token Ids not supported for now");
-        }
+        env::log(
+            json!({
+                "type": "nft_set_series_primary_split",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "primary_split": primary_split,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
 
-        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
 
-        true
+        primary_split
     }
 
+    // Series creatorship
+
     #[payable]
-    pub fn delete_mint_bundle(
+    pub fn nft_propose_series_creator(
         &mut self,
-        mint_bundle_id: MintBundleId
+        token_series_id: TokenSeriesId,
+        new_creator_id: ValidAccountId,
+        migrate_royalty: Option<bool>,
     ) {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+
+        let token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert_ne!(
+            new_creator_id.to_string(),
+            token_series.creator_id,
+            "Marble: already the creator"
+        );
+
+        let migrate_royalty = migrate_royalty.unwrap_or(false);
+        if migrate_royalty {
+            assert!(!token_series.royalty_locked, "Marble: royalty is locked");
+        }
+
+        self.series_creator_proposals.insert(&token_series_id, &SeriesCreatorProposal {
+            new_creator_id: new_creator_id.to_string(),
+            migrate_royalty,
+        });
+
+        env::log(
+            json!({
+                "type": "nft_propose_series_creator",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "creator_id": token_series.creator_id,
+                    "new_creator_id": new_creator_id,
+                    "migrate_royalty": migrate_royalty,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+    }
+
+    #[payable]
+    pub fn nft_cancel_series_creator_proposal(&mut self, token_series_id: TokenSeriesId) {
         assert_one_yocto();
-        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Marble: Only owner");
-        self.mint_bundles.remove(&mint_bundle_id);
+
+        let token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        self.series_creator_proposals.remove(&token_series_id).expect("Marble: no creator proposal");
+
+        env::log(
+            json!({
+                "type": "nft_cancel_series_creator_proposal",
+                "params": {
+                    "token_series_id": token_series_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
     }
 
     #[payable]
-    pub fn set_price_mint_bundle(
+    pub fn nft_accept_series_creator(&mut self, token_series_id: TokenSeriesId) {
+        assert_one_yocto();
+
+        let proposal = self.series_creator_proposals.get(&token_series_id).expect("Marble: no creator proposal");
+        assert_eq!(
+            env::predecessor_account_id(),
+            proposal.new_creator_id,
+            "Marble: Proposed creator only"
+        );
+        self.series_creator_proposals.remove(&token_series_id);
+
+        self.internal_set_series_creator(
+            &token_series_id,
+            proposal.new_creator_id,
+            proposal.migrate_royalty,
+            None,
+        );
+    }
+
+    /// Owner-forced reassignment, for creators who lost access to their account
+    #[payable]
+    pub fn nft_force_series_creator(
         &mut self,
-        mint_bundle_id: MintBundleId,
-        price: U128
+        token_series_id: TokenSeriesId,
+        new_creator_id: ValidAccountId,
+        migrate_royalty: Option<bool>,
     ) {
         assert_one_yocto();
-        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Marble: Only owner");
-        let mut mint_bundle = self.mint_bundles.get(&mint_bundle_id).unwrap();
-        mint_bundle.price = Some(price.0);
-        self.mint_bundles.insert(&mint_bundle_id, &mint_bundle);
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Owner only"
+        );
+        self.series_creator_proposals.remove(&token_series_id);
+
+        self.internal_set_series_creator(
+            &token_series_id,
+            new_creator_id.to_string(),
+            migrate_royalty.unwrap_or(false),
+            Some(env::predecessor_account_id()),
+        );
     }
 
+    fn internal_set_series_creator(
+        &mut self,
+        token_series_id: &TokenSeriesId,
+        new_creator_id: AccountId,
+        migrate_royalty: bool,
+        authorized_id: Option<AccountId>,
+    ) {
+        let mut token_series = self.token_series_by_id.get(token_series_id).expect("Token series not exist");
+        let old_creator_id = std::mem::replace(&mut token_series.creator_id, new_creator_id.clone());
 
-    // CUSTOM VIEWS
+        if migrate_royalty {
+            assert!(!token_series.royalty_locked, "Marble: royalty is locked");
+            if let Some(share) = token_series.royalty.remove(&old_creator_id) {
+                *token_series.royalty.entry(new_creator_id.clone()).or_insert(0) += share;
+            }
+        }
+        // primary sale proceeds follow the creator role
+        if let Some(share) = token_series.primary_split.remove(&old_creator_id) {
+            *token_series.primary_split.entry(new_creator_id.clone()).or_insert(0) += share;
+        }
 
-    pub fn get_buy_count_mint_bundle(
-        &self,
-        mint_bundle_id: MintBundleId,
-        account_id: ValidAccountId
-    ) -> u32 {
-        let mint_bundle = self.mint_bundles.get(&mint_bundle_id).unwrap();
-        mint_bundle.bought_account_ids.get(&account_id.to_string()).unwrap_or(0)
-    }
+        self.token_series_by_id.insert(token_series_id, &token_series);
+        self.internal_remove_series_from_creator(&old_creator_id, token_series_id);
+        self.internal_add_series_to_creator(&new_creator_id, token_series_id);
 
-    pub fn get_mint_bundle(
-        &self,
-        mint_bundle_id: MintBundleId
-    ) -> MintBundleJson {
-        let mint_bundle = self.mint_bundles.get(&mint_bundle_id).unwrap();
-        MintBundleJson {
-            token_series_ids: match mint_bundle.token_series_ids {
-                Some(x) => Some(x.to_vec()),
-                None => None
-            },
-            token_ids: match mint_bundle.token_ids {
-                Some(x) => Some(x.to_vec()),
-                None => None
-            },
-            price: match mint_bundle.price {
-                Some(x) => Some(U128(x)),
-                None => None
-            },
-            limit_buy: mint_bundle.limit_buy
-        }
-    }
-
-    pub fn nft_get_series_single(&self, token_series_id: TokenSeriesId) -> TokenSeriesJson {
-        let token_series = self.token_series_by_id.get(&token_series_id).expect("Series does not exist");
-        TokenSeriesJson {
-            token_series_id,
-            metadata: token_series.metadata,
-            creator_id: token_series.creator_id,
-            royalty: token_series.royalty,
-        }
-    }
-
-    pub fn nft_get_series_format(self) -> (char, &'static str, &'static str) {
-        (TOKEN_DELIMETER, TITLE_DELIMETER, EDITION_DELIMETER)
+        env::log(
+            json!({
+                "type": "nft_set_series_creator",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "old_creator_id": old_creator_id,
+                    "creator_id": new_creator_id,
+                    "royalty": token_series.royalty,
+                    "primary_split": token_series.primary_split,
+                    "authorized_id": authorized_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
     }
 
-    pub fn nft_get_series_price(self, token_series_id: TokenSeriesId) -> Option<U128> {
-        let price = self.token_series_by_id.get(&token_series_id).unwrap().price;
-        match price {
-            Some(p) => return Some(U128::from(p)),
-            None => return None
+    /// Title, media, reference and the validity window keep coming from the series or what the
+    /// creator minted with
+    #[payable]
+    pub fn nft_change_metadata(&mut self, token_id: TokenId, metadata: TokenMetadata) {
+        assert_one_yocto();
+        let owner_id = self.tokens.owner_by_id.get(&token_id).unwrap();
+        assert_eq!(
+            owner_id,
+            env::predecessor_account_id(),
+            "Token owner only"
+        );
+        let token_data = match self.token_data_by_id.get(&token_id) {
+            Some(token_data) => token_data,
+            None => self
+                .tokens
+                .token_metadata_by_id
+                .as_mut()
+                .and_then(|by_id| by_id.remove(&token_id))
+                .map(compact_token_metadata)
+                .unwrap_or(TokenData { issued_at: None, overrides: None }),
         };
+        self.token_data_by_id.insert(&token_id, &TokenData {
+            issued_at: token_data.issued_at,
+            overrides: Some(keep_creator_fields(metadata, token_data.overrides.as_ref())),
+        });
     }
 
-    pub fn nft_get_series(
-        &self,
-        from_index: Option<U128>,
-        limit: Option<u64>,
-    ) -> Vec<TokenSeriesJson> {
-        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
-        assert!(
-            (self.token_series_by_id.len() as u128) > start_index,
-            "Out of bounds, please use a smaller from_index."
-        );
-        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
-        assert_ne!(limit, 0, "Cannot provide limit of 0.");
+    #[payable]
+    pub fn nft_burn(&mut self, token_id: TokenId) {
+        assert_one_yocto();
 
-        self.token_series_by_id
-            .iter()
-            .skip(start_index as usize)
-            .take(limit)
-            .map(|(token_series_id, token_series)| TokenSeriesJson {
-                token_series_id,
-                metadata: token_series.metadata,
-                creator_id: token_series.creator_id,
-                royalty: token_series.royalty,
-            })
-            .collect()
-    }
+        let owner_id = self.tokens.owner_by_id.get(&token_id).unwrap();
+        assert_eq!(
+            owner_id,
+            env::predecessor_account_id(),
+            "Token owner only"
+        );
 
-    pub fn nft_supply_for_series(&self, token_series_id: TokenSeriesId) -> U64 {
-        self.token_series_by_id.get(&token_series_id).expect("Token series not exist").tokens.len().into()
-    }
+        self.internal_burn(&token_id, &owner_id);
 
-    pub fn nft_tokens_by_series(
-        &self,
-        token_series_id: TokenSeriesId,
-        from_index: Option<U128>,
-        limit: Option<u64>,
-    ) -> Vec<Token> {
-        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
-        let tokens = self.token_series_by_id.get(&token_series_id).unwrap().tokens;
-        assert!(
-            (tokens.len() as u128) > start_index,
-            "Out of bounds, please use a smaller from_index."
+        NearEvent::log_nft_burn(
+            owner_id,
+            vec![token_id],
+            None,
+            None,
         );
-        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
-        assert_ne!(limit, 0, "Cannot provide limit of 0.");
-
-        tokens
-            .iter()
-            .skip(start_index as usize)
-            .take(limit)
-            .map(|token_id| self.nft_token(token_id).unwrap())
-            .collect()
     }
 
-    pub fn nft_token(&self, token_id: TokenId) -> Option<Token> {
-        let owner_id = self.tokens.owner_by_id.get(&token_id)?;
-        let approved_account_ids = self
-            .tokens
-            .approvals_by_id
-            .as_ref()
-            .and_then(|by_id| by_id.get(&token_id).or_else(|| Some(HashMap::new())));
-
-        // CUSTOM (switch metadata for the token_series metadata)
-        let mut token_id_iter = token_id.split(TOKEN_DELIMETER);
-        let token_series_id = token_id_iter.next().unwrap().parse().unwrap();
-        let series_metadata = self.token_series_by_id.get(&token_series_id).unwrap().metadata;
+    /// Burns a revocable soulbound token on behalf of the series creator, e.g. an expired membership
+    #[payable]
+    pub fn nft_revoke_soulbound(&mut self, token_id: TokenId, memo: Option<String>) {
+        assert_one_yocto();
 
-        let mut token_metadata = self.tokens.token_metadata_by_id.as_ref().unwrap().get(&token_id).unwrap();
+        let token_series = self.token_series_by_id.get(&get_token_series_id(&token_id)).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert_eq!(
+            token_series.transferability,
+            Transferability::RevocableSoulbound,
+            "Marble: token is not revocable"
+        );
+        let owner_id = self.tokens.owner_by_id.get(&token_id).expect("Token not found");
 
-        token_metadata.title = Some(format!(
-            "{}{}{}",
-            series_metadata.title.unwrap(),
-            TITLE_DELIMETER,
-            token_id_iter.next().unwrap()
-        ));
+        self.internal_burn(&token_id, &owner_id);
 
-        // token_metadata.reference = series_metadata.reference;
-        // token_metadata.media = series_metadata.media;
-        // token_metadata.copies = series_metadata.copies;
-        token_metadata.extra = series_metadata.reference;
+        env::log(
+            json!({
+                "type": "nft_revoke_soulbound",
+                "params": {
+                    "token_id": token_id,
+                    "owner_id": owner_id,
+                    "creator_id": token_series.creator_id,
+                    "memo": memo,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
 
-        Some(Token {
-            token_id,
+        NearEvent::log_nft_burn(
             owner_id,
-            metadata: Some(token_metadata),
-            approved_account_ids,
-        })
+            vec![token_id],
+            memo,
+            Some(token_series.creator_id),
+        );
     }
 
-    // CUSTOM core standard repeated here because no macro below
-
-    pub fn nft_transfer_unsafe(
+    /// Every transfer goes through here so contract-side indexes follow the token
+    fn internal_transfer(
         &mut self,
-        receiver_id: ValidAccountId,
-        token_id: TokenId,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
         approval_id: Option<u64>,
         memo: Option<String>,
-    ) {
-        let sender_id = env::predecessor_account_id();
-        let receiver_id_str = receiver_id.to_string();
-        let (previous_owner_id, _) = self.tokens.internal_transfer(&sender_id, &receiver_id_str, &token_id, approval_id, memo.clone());
-
-        let authorized_id: Option<AccountId> = if sender_id != previous_owner_id {
-            Some(sender_id)
-        } else {
-            None
-        };
-
-        NearEvent::log_nft_transfer(
-            previous_owner_id,
-            receiver_id_str,
-            vec![token_id],
-            memo,
-            authorized_id,
-        );
+    ) -> (AccountId, Option<HashMap<AccountId, u64>>) {
+        self.internal_transfer_at_price(sender_id, receiver_id, token_id, approval_id, memo, None)
     }
 
-    #[payable]
-    pub fn nft_transfer(
+    /// `balance` is the sale price checked against the approval's `min_price`
+    fn internal_transfer_at_price(
         &mut self,
-        receiver_id: ValidAccountId,
-        token_id: TokenId,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
         approval_id: Option<u64>,
         memo: Option<String>,
-    ) {
-        let sender_id = env::predecessor_account_id();
-        let previous_owner_id = self.tokens.owner_by_id.get(&token_id).expect("Token not found");
-        let receiver_id_str = receiver_id.to_string();
-        self.tokens.nft_transfer(receiver_id, token_id.clone(), approval_id, memo.clone());
-
-        let authorized_id: Option<AccountId> = if sender_id != previous_owner_id {
-            Some(sender_id)
-        } else {
-            None
-        };
+        balance: Option<Balance>,
+    ) -> (AccountId, Option<HashMap<AccountId, u64>>) {
+        self.assert_transferable(token_id);
+        self.internal_transfer_unchecked(sender_id, receiver_id, token_id, approval_id, memo, balance)
+    }
 
-        NearEvent::log_nft_transfer(
-            previous_owner_id,
-            receiver_id_str,
-            vec![token_id],
-            memo,
-            authorized_id,
-        );
+    /// Returns a token held by the contract for nesting or a vault, whatever its series' transfer policy
+    fn internal_release_custody(&mut self, receiver_id: &AccountId, token_id: &TokenId, memo: Option<String>) {
+        let contract_id = env::current_account_id();
+        self.internal_transfer_unchecked(&contract_id, receiver_id, token_id, None, memo, None);
     }
 
-    #[payable]
-    pub fn nft_transfer_call(
+    fn internal_transfer_unchecked(
         &mut self,
-        receiver_id: ValidAccountId,
-        token_id: TokenId,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
         approval_id: Option<u64>,
         memo: Option<String>,
-        msg: String,
-    ) -> PromiseOrValue<bool> {
-        assert_one_yocto();
-        let sender_id = env::predecessor_account_id();
-        let (previous_owner_id, old_approvals) = self.tokens.internal_transfer(
-            &sender_id,
-            receiver_id.as_ref(),
-            &token_id,
-            approval_id,
-            memo.clone(),
-        );
-
-        let authorized_id: Option<AccountId> = if sender_id != previous_owner_id {
-            Some(sender_id.clone())
+        balance: Option<Balance>,
+    ) -> (AccountId, Option<HashMap<AccountId, u64>>) {
+        // operators act with the owner's authority
+        let owner_id = self.tokens.owner_by_id.get(token_id).expect("Token not found");
+        let sender_id = if sender_id == &owner_id {
+            sender_id
+        } else if self.internal_is_operator(&owner_id, sender_id, token_id) {
+            &owner_id
         } else {
-            None
+            self.assert_approval_terms(token_id, sender_id, balance);
+            sender_id
         };
+        let (previous_owner_id, old_approvals) =
+            self.tokens.internal_transfer(sender_id, receiver_id, token_id, approval_id, memo);
+        self.approval_terms_by_id.remove(token_id);
+        self.internal_on_owner_change(token_id, Some(&previous_owner_id), Some(receiver_id));
+        self.internal_clear_token_user(token_id);
+        (previous_owner_id, old_approvals)
+    }
 
-        NearEvent::log_nft_transfer(
-            previous_owner_id.clone(),
-            receiver_id.to_string(),
-            vec![token_id.clone()],
-            memo,
-            authorized_id,
-        );
+    fn assert_transferable(&self, token_id: &TokenId) {
+        self.assert_not_staked(token_id);
+        let token_series = self.token_series_by_id.get(&get_token_series_id(token_id)).expect("Token series not exist");
+        assert_eq!(token_series.transferability, Transferability::Transferable, "Marble: token is soulbound");
+        if token_series.validity.map(|validity| validity.block_transfer_when_expired).unwrap_or(false) {
+            assert!(!self.internal_is_expired(token_id), "Marble: token is expired");
+        }
+    }
 
-        // Initiating receiver's call and the callback
-        ext_non_fungible_token_receiver::nft_on_transfer(
-            sender_id,
-            previous_owner_id.clone(),
-            token_id.clone(),
-            msg,
-            receiver_id.as_ref(),
-            NO_DEPOSIT,
-            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
-        )
-            .then(ext_self::nft_resolve_transfer(
-                previous_owner_id,
-                receiver_id.into(),
-                token_id,
-                old_approvals,
-                &env::current_account_id(),
-                NO_DEPOSIT,
-                GAS_FOR_RESOLVE_TRANSFER,
-            ))
-            .into()
+    /// Keeps `owners_by_series` and snapshots in progress in sync. `from` is None on mint and `to` is None on burn.
+    fn internal_on_owner_change(&mut self, token_id: &TokenId, from: Option<&AccountId>, to: Option<&AccountId>) {
+        self.internal_settle_snapshots(token_id, from);
+        // a token the backfill hasn't reached yet isn't counted for its previous owner
+        let from = from.filter(|from| !self.owners_backfill_pending || self.is_owner_indexed(from, token_id));
+        self.internal_index_owner_change(token_id, from, to);
     }
 
-    // CUSTOM enumeration standard modified here because no macro below
+    fn internal_index_owner_change(&mut self, token_id: &TokenId, from: Option<&AccountId>, to: Option<&AccountId>) {
+        let token_series_id = get_token_series_id(token_id);
+        let mut owners = self.owners_by_series.get(&token_series_id).unwrap_or_else(|| {
+            UnorderedMap::new(StorageKey::OwnersBySeriesInner {
+                token_series: token_series_id.clone(),
+            })
+        });
 
-    pub fn nft_total_supply(&self) -> U128 {
-        (self.tokens.owner_by_id.len() as u128).into()
-    }
+        if let Some(from) = from {
+            match owners.get(from) {
+                Some(count) if count > 1 => {
+                    owners.insert(from, &(count - 1));
+                }
+                _ => {
+                    owners.remove(from);
+                }
+            }
+        }
 
-    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
-        // Get starting index, whether or not it was explicitly given.
-        // Defaults to 0 based on the spec:
-        // https://nomicon.io/Standards/NonFungibleToken/Enumeration.html#interface
-        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
-        assert!(
-            (self.tokens.owner_by_id.len() as u128) > start_index,
-            "Out of bounds, please use a smaller from_index."
-        );
-        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
-        assert_ne!(limit, 0, "Cannot provide limit of 0.");
-        self.tokens
-            .owner_by_id
-            .iter()
-            .skip(start_index as usize)
-            .take(limit)
-            .map(|(token_id, _)| self.nft_token(token_id).unwrap())
-            .collect()
+        if let Some(to) = to {
+            let count = owners.get(to).unwrap_or(0);
+            owners.insert(to, &(count + 1));
+        }
+
+        self.owners_by_series.insert(&token_series_id, &owners);
+
+        if let Some(from) = from {
+            if let Some(mut token_ids) = self.token_ids_by_owner.get(from) {
+                token_ids.remove(token_id);
+                if token_ids.len() == 0 {
+                    self.token_ids_by_owner.remove(from);
+                } else {
+                    self.token_ids_by_owner.insert(from, &token_ids);
+                }
+            }
+        }
+        if let Some(to) = to {
+            let mut token_ids = self.token_ids_by_owner.get(to).unwrap_or_else(|| {
+                TreeMap::new(StorageKey::TokenIdsByOwnerInner {
+                    account_hash: env::sha256(to.as_bytes()),
+                })
+            });
+            token_ids.insert(token_id, &());
+            self.token_ids_by_owner.insert(to, &token_ids);
+        }
     }
 
-    pub fn nft_supply_for_owner(self, account_id: ValidAccountId) -> U128 {
-        let tokens_per_owner = self.tokens.tokens_per_owner.expect(
-            "Could not find tokens_per_owner when calling a method on the enumeration standard.",
-        );
-        tokens_per_owner
-            .get(account_id.as_ref())
-            .map(|account_tokens| U128::from(account_tokens.len() as u128))
-            .unwrap_or(U128(0))
+    fn is_owner_indexed(&self, owner_id: &AccountId, token_id: &TokenId) -> bool {
+        self.token_ids_by_owner
+            .get(owner_id)
+            .map(|token_ids| token_ids.contains_key(token_id))
+            .unwrap_or(false)
     }
 
-    pub fn nft_tokens_for_owner(
-        &self,
-        account_id: ValidAccountId,
-        from_index: Option<U128>,
-        limit: Option<u64>,
-    ) -> Vec<Token> {
-        let tokens_per_owner = self.tokens.tokens_per_owner.as_ref().expect(
-            "Could not find tokens_per_owner when calling a method on the enumeration standard.",
-        );
-        let token_set = if let Some(token_set) = tokens_per_owner.get(account_id.as_ref()) {
-            token_set
-        } else {
-            return vec![];
-        };
-        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
-        assert_ne!(limit, 0, "Cannot provide limit of 0.");
-        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
-        assert!(
-            token_set.len() as u128 > start_index,
-            "Out of bounds, please use a smaller from_index."
-        );
-        token_set
-            .iter()
-            .skip(start_index as usize)
-            .take(limit)
-            .map(|token_id| self.nft_token(token_id).unwrap())
-            .collect()
-    }
-
-    pub fn nft_payout(
-        &self,
-        token_id: TokenId,
-        balance: U128,
-        max_len_payout: u32,
-    ) -> Payout {
-        let owner_id = self.tokens.owner_by_id.get(&token_id).expect("No token id");
-        let mut token_id_iter = token_id.split(TOKEN_DELIMETER);
-        let token_series_id = token_id_iter.next().unwrap().parse().unwrap();
-        let royalty = self.token_series_by_id.get(&token_series_id).expect("no type").royalty;
+    fn internal_burn(&mut self, token_id: &TokenId, owner_id: &AccountId) {
+        self.assert_no_children(token_id);
+        self.assert_not_attached(token_id);
+        self.assert_not_vaulted(token_id);
+        self.assert_not_staked(token_id);
 
-        assert!(royalty.len() as u32 <= max_len_payout, "Market cannot payout to that many receivers");
+        if let Some(next_approval_id_by_id) = &mut self.tokens.next_approval_id_by_id {
+            next_approval_id_by_id.remove(token_id);
+        }
 
-        let balance_u128: u128 = balance.into();
+        if let Some(approvals_by_id) = &mut self.tokens.approvals_by_id {
+            approvals_by_id.remove(token_id);
+        }
+        self.approval_terms_by_id.remove(token_id);
 
-        let mut payout: Payout = Payout { payout: HashMap::new() };
-        let mut total_perpetual = 0;
+        if let Some(tokens_per_owner) = &mut self.tokens.tokens_per_owner {
+            let mut token_ids = tokens_per_owner.get(owner_id).unwrap();
+            token_ids.remove(token_id);
+            tokens_per_owner.insert(owner_id, &token_ids);
+        }
 
-        for (k, v) in royalty.iter() {
-            if *k != owner_id {
-                let key = k.clone();
-                payout.payout.insert(key, royalty_to_payout(*v, balance_u128));
-                total_perpetual += *v;
-            }
+        if let Some(token_metadata_by_id) = &mut self.tokens.token_metadata_by_id {
+            token_metadata_by_id.remove(token_id);
         }
-        payout.payout.insert(owner_id, royalty_to_payout(10000 - total_perpetual, balance_u128));
-        payout
+
+        self.token_data_by_id.remove(token_id);
+        self.token_royalty_by_id.remove(token_id);
+        self.internal_clear_token_user(token_id);
+        self.tokens.owner_by_id.remove(token_id);
+        self.internal_on_owner_change(token_id, Some(owner_id), None);
+        self.internal_remove_token_traits(token_id);
+
+        let token_series_id = get_token_series_id(token_id);
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        token_series.burned += 1;
+        self.token_series_by_id.insert(&token_series_id, &token_series);
     }
 
+    // Mint Bundles
+
     #[payable]
-    pub fn nft_transfer_payout(
+    pub fn buy_mint_bundle(
         &mut self,
+        mint_bundle_id: MintBundleId,
         receiver_id: ValidAccountId,
-        token_id: TokenId,
-        approval_id: Option<u64>,
-        balance: Option<U128>,
-        max_len_payout: Option<u32>,
-    ) -> Option<Payout> {
-        assert_one_yocto();
+    ) -> Option<TokenId> {
+        let initial_storage_usage = env::storage_usage();
+        let mut mint_bundle = self.mint_bundles.get(&mint_bundle_id).expect(
+            "Marble: Mint bundle does not exist or already finished"
+        );
 
-        let sender_id = env::predecessor_account_id();
-        // Transfer
-        let previous_token = self.nft_token(token_id.clone()).expect("no token");
-        self.tokens.nft_transfer(receiver_id.clone(), token_id.clone(), approval_id, None);
+        assert_eq!(env::predecessor_account_id(), receiver_id.to_string(), "Marble: Can only buy for caller");
 
-        // Payout calculation
-        let previous_owner_id = previous_token.owner_id;
-        let mut total_perpetual = 0;
-        let payout = if let Some(balance) = balance {
-            let balance_u128: u128 = u128::from(balance);
-            let mut payout: Payout = Payout { payout: HashMap::new() };
+        let price = mint_bundle.price.expect("Marble: Mint bundle hasn't started yet");
+        assert!(
+            env::attached_deposit() > price,
+            "Marble: Attached deposit lower than mint price"
+        );
 
-            let mut token_id_iter = token_id.split(TOKEN_DELIMETER);
-            let token_series_id = token_id_iter.next().unwrap().parse().unwrap();
-            let royalty = self.token_series_by_id.get(&token_series_id).expect("no type").royalty;
+        if let Some(limit_buy) = mint_bundle.limit_buy {
+            let mint_count = mint_bundle.bought_account_ids.get(&receiver_id.to_string()).unwrap_or(0);
+            assert!(
+                mint_count < limit_buy,
+                "Marble: Mint exhausted for account_id {}",
+                receiver_id
+            );
 
-            assert!(royalty.len() as u32 <= max_len_payout.unwrap(), "Market cannot payout to that many receivers");
-            for (k, v) in royalty.iter() {
-                let key = k.clone();
-                if key != previous_owner_id {
-                    payout.payout.insert(key, royalty_to_payout(*v, balance_u128));
-                    total_perpetual += *v;
+            mint_bundle.bought_account_ids.insert(
+                &receiver_id.to_string(),
+                &(mint_count + 1)
+            );
+        }
+
+        return if let Some(mut token_series_ids) = mint_bundle.token_series_ids {
+            // series whose sale ended are dropped when picked, the same way sold out ones are
+            let mut picked = None;
+            while picked.is_none() && token_series_ids.len() > 0 {
+                let seed_num = get_random_number(token_series_ids.len() as u32) as u64;
+                let index = seed_num % token_series_ids.len();
+                let token_series_id = token_series_ids.get(index).unwrap();
+                let token_series = self.token_series_by_id.get(&token_series_id).expect("Marble: Token series not exist");
+                if is_sale_ended(&token_series) {
+                    token_series_ids.swap_remove(index);
+                } else {
+                    assert_sale_open(&token_series);
+                    picked = Some((index, token_series_id));
                 }
             }
 
-            assert!(
-                total_perpetual <= 10000,
-                "Total payout overflow"
+            let (index, token_series_id) = match picked {
+                Some(picked) => picked,
+                None => {
+                    self.mint_bundles.remove(&mint_bundle_id);
+                    Promise::new(env::predecessor_account_id()).transfer(env::attached_deposit());
+                    return None;
+                }
+            };
+            let token_id = self._nft_mint_series(token_series_id.clone(), receiver_id.to_string(), None, None);
+
+            let token_series = self.token_series_by_id.get(&token_series_id.to_string()).unwrap();
+            if !token_series.is_mintable {
+                token_series_ids.swap_remove(index);
+            }
+
+            if token_series_ids.len() == 0 {
+                self.mint_bundles.remove(&mint_bundle_id);
+            } else {
+                mint_bundle.token_series_ids = Some(token_series_ids);
+                self.mint_bundles.insert(&mint_bundle_id, &mint_bundle);
+            }
+
+            let primary_split = if price > 0 {
+                self.internal_pay_primary_sale(&token_series, price)
+            } else {
+                HashMap::new()
+            };
+
+            refund_deposit(env::storage_usage() - initial_storage_usage, price);
+
+            NearEvent::log_nft_mint(
+                receiver_id.to_string(),
+                vec![token_id.clone()],
+                Some(json!({"price": price.to_string(), "primary_split": primary_split}).to_string()),
             );
 
-            payout.payout.insert(previous_owner_id.clone(), royalty_to_payout(10000 - total_perpetual, balance_u128));
-            Some(payout)
+            Some(token_id)
         } else {
             None
-        };
+        }
+    }
 
-        let authorized_id: Option<AccountId> = if sender_id != previous_owner_id {
-            Some(sender_id)
-        } else {
-            None
-        };
+    #[payable]
+    pub fn create_mint_bundle(
+        &mut self,
+        mint_bundle_id: MintBundleId,
+        token_series_ids: Option<Vec<TokenSeriesId>>,
+        token_ids: Option<Vec<TokenId>>,
+        price: Option<U128>,
+        limit_buy: Option<u32>,
+    ) -> bool {
+        let initial_storage_usage = env::storage_usage();
 
-        NearEvent::log_nft_transfer(
-            previous_owner_id,
-            receiver_id.to_string(),
-            vec![token_id],
-            None,
-            authorized_id,
-        );
+        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Marble: Only owner");
 
-        payout
-    }
+        let mint_bundle = self.mint_bundles.get(&mint_bundle_id);
 
-    pub fn get_owner(&self) -> AccountId {
-        self.tokens.owner_id.clone()
-    }
-}
+        if mint_bundle.is_some() {
+            panic!("Mint bundle already exists");
+        }
 
-fn royalty_to_payout(a: u32, b: Balance) -> U128 {
-    U128(a as u128 * b / 10_000u128)
-}
+        if token_series_ids.is_some() {
+            assert!(
+                token_ids.is_none(),
+                "Must chose either token_series_ids or token_ids"
+            );
+            let mut token_series_ids_internal: Vector<TokenSeriesId> = Vector::new(
+                StorageKey::MintBundleTokens { mint_bundle_id: mint_bundle_id.clone() }
+            );
+            for token_series_id in token_series_ids.unwrap() {
+                token_series_ids_internal.push(&token_series_id);
+            }
+            self.mint_bundles.insert(&mint_bundle_id.clone(), &MintBundle {
+                token_series_ids: Some(token_series_ids_internal),
+                token_ids: None,
+                price: match price {
+                    Some(x) => Some(x.0),
+                    None => None
+                },
+                limit_buy,
+                bought_account_ids: LookupMap::new(StorageKey::BoughtAccountId { mint_bundle_id }),
+            });
+        } else if token_ids.is_some() {
+            assert!(
+                token_series_ids.is_none(),
+                "Must chose either token_series_ids or token_ids"
+            );
+            panic!("Token Ids not supported for now");
+        }
 
-// near_contract_standards::impl_non_fungible_token_core!(Contract, tokens);
-// near_contract_standards::impl_non_fungible_token_enumeration!(Contract, tokens);
-near_contract_standards::impl_non_fungible_token_approval!(Contract, tokens);
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
 
-#[near_bindgen]
-impl NonFungibleTokenMetadataProvider for Contract {
-    fn nft_metadata(&self) -> NFTContractMetadata {
-        self.metadata.get().unwrap()
+        true
     }
-}
 
-#[near_bindgen]
-impl NonFungibleTokenResolver for Contract {
-    #[private]
-    fn nft_resolve_transfer(
+    #[payable]
+    pub fn delete_mint_bundle(
         &mut self,
-        previous_owner_id: AccountId,
-        receiver_id: AccountId,
-        token_id: TokenId,
-        approved_account_ids: Option<HashMap<AccountId, u64>>,
-    ) -> bool {
-        let resp: bool = self.tokens.nft_resolve_transfer(
-            previous_owner_id.clone(),
-            receiver_id.clone(),
-            token_id.clone(),
-            approved_account_ids,
-        );
-
-        // if not successful, return nft back to original owner
-        if !resp {
-            NearEvent::log_nft_transfer(
-                receiver_id,
-                previous_owner_id,
-                vec![token_id],
-                None,
-                None,
-            );
-        }
+        mint_bundle_id: MintBundleId
+    ) {
+        assert_one_yocto();
+        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Marble: Only owner");
+        self.mint_bundles.remove(&mint_bundle_id);
+    }
 
-        resp
+    #[payable]
+    pub fn set_price_mint_bundle(
+        &mut self,
+        mint_bundle_id: MintBundleId,
+        price: U128
+    ) {
+        assert_one_yocto();
+        assert_eq!(env::predecessor_account_id(), self.tokens.owner_id, "Marble: Only owner");
+        let mut mint_bundle = self.mint_bundles.get(&mint_bundle_id).unwrap();
+        mint_bundle.price = Some(price.0);
+        self.mint_bundles.insert(&mint_bundle_id, &mint_bundle);
     }
-}
 
-/// from https://github.com/near/near-sdk-rs/blob/e4abb739ff953b06d718037aa1b8ab768db17348/near-contract-standards/src/non_fungible_token/utils.rs#L29
 
-fn refund_deposit(storage_used: u64, extra_spend: Balance) {
-    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
-    let attached_deposit = env::attached_deposit() - extra_spend;
+    // CUSTOM VIEWS
+
+    pub fn get_buy_count_mint_bundle(
+        &self,
+        mint_bundle_id: MintBundleId,
+        account_id: ValidAccountId
+    ) -> u32 {
+        let mint_bundle = self.mint_bundles.get(&mint_bundle_id).unwrap();
+        mint_bundle.bought_account_ids.get(&account_id.to_string()).unwrap_or(0)
+    }
+
+    pub fn get_mint_bundle(
+        &self,
+        mint_bundle_id: MintBundleId
+    ) -> MintBundleJson {
+        let mint_bundle = self.mint_bundles.get(&mint_bundle_id).unwrap();
+        MintBundleJson {
+            token_series_ids: match mint_bundle.token_series_ids {
+                Some(x) => Some(x.to_vec()),
+                None => None
+            },
+            token_ids: match mint_bundle.token_ids {
+                Some(x) => Some(x.to_vec()),
+                None => None
+            },
+            price: match mint_bundle.price {
+                Some(x) => Some(U128(x)),
+                None => None
+            },
+            limit_buy: mint_bundle.limit_buy
+        }
+    }
+
+    pub fn nft_get_series_single(&self, token_series_id: TokenSeriesId) -> Option<TokenSeriesJson> {
+        self.token_series_by_id
+            .get(&token_series_id)
+            .map(|token_series| token_series_to_json(token_series_id, token_series))
+    }
+
+    pub fn nft_get_series_creator_proposal(&self, token_series_id: TokenSeriesId) -> Option<SeriesCreatorProposalJson> {
+        self.series_creator_proposals
+            .get(&token_series_id)
+            .map(|proposal| SeriesCreatorProposalJson {
+                token_series_id,
+                new_creator_id: proposal.new_creator_id,
+                migrate_royalty: proposal.migrate_royalty,
+            })
+    }
+
+    pub fn nft_get_series_format(self) -> (char, &'static str, &'static str) {
+        (TOKEN_DELIMETER, TITLE_DELIMETER, EDITION_DELIMETER)
+    }
+
+    pub fn nft_get_series_price(&self, token_series_id: TokenSeriesId) -> Option<U128> {
+        self.token_series_by_id
+            .get(&token_series_id)
+            .and_then(|token_series| token_series.price)
+            .map(U128::from)
+    }
+
+    pub fn nft_get_series(
+        &self,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenSeriesJson> {
+        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        if limit == 0 {
+            return vec![];
+        }
+
+        self.token_series_by_id
+            .iter()
+            .skip(start_index as usize)
+            .take(limit)
+            .map(|(token_series_id, token_series)| token_series_to_json(token_series_id, token_series))
+            .collect()
+    }
+
+    /// Cursor is the last token_series_id of the previous page
+    pub fn nft_get_series_cursor(
+        &self,
+        cursor: Option<TokenSeriesId>,
+        limit: Option<u64>,
+    ) -> TokenSeriesPage {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT) as usize;
+        let start_id: u64 = cursor
+            .map(|token_series_id| token_series_id.parse().expect("Marble: invalid cursor"))
+            .unwrap_or(0)
+            + 1;
+
+        // token_series_id is assigned sequentially from 1 and series are never removed
+        let mut series: Vec<TokenSeriesJson> = (start_id..=self.token_series_by_id.len())
+            .take(limit + 1)
+            .filter_map(|id| {
+                let token_series_id = id.to_string();
+                self.token_series_by_id
+                    .get(&token_series_id)
+                    .map(|token_series| token_series_to_json(token_series_id, token_series))
+            })
+            .collect();
+        let next_cursor = page_cursor(&mut series, limit).map(|series| series.token_series_id.clone());
+
+        TokenSeriesPage { series, next_cursor }
+    }
+
+    pub fn nft_series_count_for_creator(&self, creator_id: ValidAccountId) -> U64 {
+        self.series_by_creator
+            .get(creator_id.as_ref())
+            .map(|series_ids| series_ids.len())
+            .unwrap_or(0)
+            .into()
+    }
+
+    pub fn nft_get_series_by_creator(
+        &self,
+        creator_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenSeriesJson> {
+        let series_ids = if let Some(series_ids) = self.series_by_creator.get(creator_id.as_ref()) {
+            series_ids
+        } else {
+            return vec![];
+        };
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        if limit == 0 {
+            return vec![];
+        }
+        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
+        series_ids
+            .iter()
+            .skip(start_index as usize)
+            .take(limit)
+            .map(|token_series_id| {
+                let token_series = self.token_series_by_id.get(&token_series_id).unwrap();
+                token_series_to_json(token_series_id, token_series)
+            })
+            .collect()
+    }
+
+    pub fn nft_supply_for_series(&self, token_series_id: TokenSeriesId) -> U64 {
+        self.token_series_by_id.get(&token_series_id).expect("Token series not exist").tokens.len().into()
+    }
+
+    pub fn nft_tokens_by_series(
+        &self,
+        token_series_id: TokenSeriesId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenJson> {
+        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
+        let tokens = if let Some(token_series) = self.token_series_by_id.get(&token_series_id) {
+            token_series.tokens
+        } else {
+            return vec![];
+        };
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        if limit == 0 {
+            return vec![];
+        }
+
+        tokens
+            .iter()
+            .skip(start_index as usize)
+            .take(limit)
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+
+    /// Cursor is the last token id of the previous page, e.g. "42:7". Pages past a run of burned
+    /// editions can come back short of `limit`, keep going while `next_cursor` is set.
+    pub fn nft_tokens_by_series_cursor(
+        &self,
+        token_series_id: TokenSeriesId,
+        cursor: Option<TokenId>,
+        limit: Option<u64>,
+    ) -> TokensPage {
+        let minted = match self.token_series_by_id.get(&token_series_id) {
+            Some(token_series) => token_series.tokens.len(),
+            None => return TokensPage { tokens: vec![], next_cursor: None },
+        };
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT) as usize;
+        let start_edition: u64 = cursor
+            .map(|token_id| {
+                token_id
+                    .split(TOKEN_DELIMETER)
+                    .nth(1)
+                    .and_then(|edition| edition.parse().ok())
+                    .expect("Marble: invalid cursor")
+            })
+            .unwrap_or(0)
+            + 1;
+
+        let last_edition = std::cmp::min(minted, start_edition.saturating_add(MAX_EDITIONS_SCANNED - 1));
+
+        let mut tokens: Vec<TokenJson> = (start_edition..=last_edition)
+            .filter_map(|edition| {
+                self.nft_token(format!("{}{}{}", token_series_id, TOKEN_DELIMETER, edition))
+            })
+            .take(limit + 1)
+            .collect();
+        let next_cursor = if tokens.len() > limit {
+            page_cursor(&mut tokens, limit).map(|token| token.token_id.clone())
+        } else if last_edition < minted {
+            // burned editions filled the scan, carry on after the last edition looked at
+            Some(format!("{}{}{}", token_series_id, TOKEN_DELIMETER, last_edition))
+        } else {
+            None
+        };
+
+        TokensPage { tokens, next_cursor }
+    }
+
+    /// Distinct owners of a series with how many of its tokens each holds
+    pub fn nft_owners_for_series(
+        &self,
+        token_series_id: TokenSeriesId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<OwnerCountJson> {
+        let owners = match self.owners_by_series.get(&token_series_id) {
+            Some(owners) => owners,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), owners.len());
+
+        let account_ids = owners.keys_as_vector();
+        let counts = owners.values_as_vector();
+        (start_index..end_index)
+            .map(|index| OwnerCountJson {
+                account_id: account_ids.get(index).unwrap(),
+                count: U64::from(counts.get(index).unwrap()),
+            })
+            .collect()
+    }
+
+    pub fn nft_owners_count_for_series(&self, token_series_id: TokenSeriesId) -> U64 {
+        self.owners_by_series
+            .get(&token_series_id)
+            .map(|owners| owners.len())
+            .unwrap_or(0)
+            .into()
+    }
+
+    /// Number of `token_series_id` tokens held by `account_id`
+    pub fn nft_balance_of_series(&self, account_id: ValidAccountId, token_series_id: TokenSeriesId) -> U64 {
+        self.owners_by_series
+            .get(&token_series_id)
+            .and_then(|owners| owners.get(account_id.as_ref()))
+            .unwrap_or(0)
+            .into()
+    }
+
+    /// For token-gated contracts: call it from a promise and read the bool in the callback
+    pub fn nft_holds_at_least(
+        &self,
+        account_id: ValidAccountId,
+        token_series_id: TokenSeriesId,
+        min_balance: U64,
+    ) -> bool {
+        self.nft_balance_of_series(account_id, token_series_id).0 >= min_balance.0
+    }
+
+    pub fn nft_token(&self, token_id: TokenId) -> Option<TokenJson> {
+        let owner_id = self.tokens.owner_by_id.get(&token_id)?;
+        let approved_account_ids = self
+            .tokens
+            .approvals_by_id
+            .as_ref()
+            .and_then(|by_id| by_id.get(&token_id).or_else(|| Some(HashMap::new())))
+            .map(|approvals| {
+                approvals
+                    .into_iter()
+                    .filter(|(account_id, _)| !self.internal_is_approval_expired(&token_id, account_id))
+                    .collect()
+            });
+
+        // CUSTOM (fill unset token fields from the token_series metadata)
+        let mut token_id_iter = token_id.split(TOKEN_DELIMETER);
+        let token_series_id: TokenSeriesId = token_id_iter.next().unwrap().to_string();
+        let edition = token_id_iter.next().unwrap();
+        let token_series = self.token_series_by_id.get(&token_series_id).unwrap();
+
+        let token_metadata = inherit_metadata(
+            self.internal_get_token_metadata(&token_id),
+            token_series.metadata,
+            token_series.title_format,
+            edition,
+        );
+
+        Some(TokenJson {
+            traits: self.token_traits_by_id.get(&token_id),
+            token_id,
+            owner_id,
+            metadata: Some(token_metadata),
+            approved_account_ids,
+        })
+    }
+
+    #[payable]
+    pub fn nft_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let receiver_id_str = receiver_id.to_string();
+        let (previous_owner_id, _) = self.internal_transfer(&sender_id, &receiver_id_str, &token_id, approval_id, memo.clone());
+
+        let authorized_id: Option<AccountId> = if sender_id != previous_owner_id {
+            Some(sender_id)
+        } else {
+            None
+        };
+
+        NearEvent::log_nft_transfer(
+            previous_owner_id,
+            receiver_id_str,
+            vec![token_id],
+            memo,
+            authorized_id,
+        );
+    }
+
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let old_approval_terms = self.approval_terms_by_id.get(&token_id);
+        let (previous_owner_id, old_approvals) = self.internal_transfer(
+            &sender_id,
+            receiver_id.as_ref(),
+            &token_id,
+            approval_id,
+            memo.clone(),
+        );
+
+        let authorized_id: Option<AccountId> = if sender_id != previous_owner_id {
+            Some(sender_id.clone())
+        } else {
+            None
+        };
+
+        NearEvent::log_nft_transfer(
+            previous_owner_id.clone(),
+            receiver_id.to_string(),
+            vec![token_id.clone()],
+            memo,
+            authorized_id,
+        );
+
+        // Initiating receiver's call and the callback
+        ext_non_fungible_token_receiver::nft_on_transfer(
+            sender_id,
+            previous_owner_id.clone(),
+            token_id.clone(),
+            msg,
+            receiver_id.as_ref(),
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
+        )
+            .then(ext_self::nft_resolve_transfer(
+                previous_owner_id,
+                receiver_id.into(),
+                token_id,
+                old_approvals,
+                old_approval_terms,
+                &env::current_account_id(),
+                NO_DEPOSIT,
+                GAS_FOR_RESOLVE_TRANSFER,
+            ))
+            .into()
+    }
+
+    // CUSTOM enumeration standard modified here because no macro below
+
+    pub fn nft_total_supply(&self) -> U128 {
+        (self.tokens.owner_by_id.len() as u128).into()
+    }
+
+    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<TokenJson> {
+        // Get starting index, whether or not it was explicitly given.
+        // Defaults to 0 based on the spec:
+        // https://nomicon.io/Standards/NonFungibleToken/Enumeration.html#interface
+        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        if limit == 0 {
+            return vec![];
+        }
+        self.tokens
+            .owner_by_id
+            .iter()
+            .skip(start_index as usize)
+            .take(limit)
+            .map(|(token_id, _)| self.nft_token(token_id).unwrap())
+            .collect()
+    }
+
+    /// Cursor is the last token id of the previous page
+    pub fn nft_tokens_cursor(&self, cursor: Option<TokenId>, limit: Option<u64>) -> TokensPage {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT) as usize;
+        let token_ids: Vec<TokenId> = match cursor {
+            Some(cursor) => self.tokens.owner_by_id.range((Bound::Excluded(cursor), Bound::Unbounded)).map(|(token_id, _)| token_id).take(limit + 1).collect(),
+            None => self.tokens.owner_by_id.iter().map(|(token_id, _)| token_id).take(limit + 1).collect(),
+        };
+
+        let mut tokens: Vec<TokenJson> = token_ids
+            .into_iter()
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect();
+        let next_cursor = page_cursor(&mut tokens, limit).map(|token| token.token_id.clone());
+
+        TokensPage { tokens, next_cursor }
+    }
+
+    pub fn nft_supply_for_owner(self, account_id: ValidAccountId) -> U128 {
+        let tokens_per_owner = self.tokens.tokens_per_owner.expect(
+            "Could not find tokens_per_owner when calling a method on the enumeration standard.",
+        );
+        tokens_per_owner
+            .get(account_id.as_ref())
+            .map(|account_tokens| U128::from(account_tokens.len() as u128))
+            .unwrap_or(U128(0))
+    }
+
+    pub fn nft_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenJson> {
+        let tokens_per_owner = self.tokens.tokens_per_owner.as_ref().expect(
+            "Could not find tokens_per_owner when calling a method on the enumeration standard.",
+        );
+        let token_set = if let Some(token_set) = tokens_per_owner.get(account_id.as_ref()) {
+            token_set
+        } else {
+            return vec![];
+        };
+        let limit = limit.map(|v| v as usize).unwrap_or(usize::MAX);
+        if limit == 0 {
+            return vec![];
+        }
+        let start_index: u128 = from_index.map(From::from).unwrap_or_default();
+        token_set
+            .iter()
+            .skip(start_index as usize)
+            .take(limit)
+            .map(|token_id| self.nft_token(token_id).unwrap())
+            .collect()
+    }
+
+    /// Cursor is the last token id of the previous page
+    pub fn nft_tokens_for_owner_cursor(
+        &self,
+        account_id: ValidAccountId,
+        cursor: Option<TokenId>,
+        limit: Option<u64>,
+    ) -> TokensPage {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT) as usize;
+        let owner_token_ids = match self.token_ids_by_owner.get(account_id.as_ref()) {
+            Some(owner_token_ids) => owner_token_ids,
+            None => return TokensPage { tokens: vec![], next_cursor: None },
+        };
+        let start = match cursor {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Unbounded,
+        };
+        let token_ids: Vec<TokenId> = owner_token_ids
+            .range((start, Bound::Unbounded))
+            .map(|(token_id, _)| token_id)
+            .take(limit + 1)
+            .collect();
+
+        let mut tokens: Vec<TokenJson> = token_ids
+            .into_iter()
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect();
+        let next_cursor = page_cursor(&mut tokens, limit).map(|token| token.token_id.clone());
+
+        TokensPage { tokens, next_cursor }
+    }
+
+    pub fn nft_payout(
+        &self,
+        token_id: TokenId,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Payout {
+        let owner_id = self.tokens.owner_by_id.get(&token_id).expect("No token id");
+        let royalty = self.internal_get_royalty(&token_id);
+
+        assert!(royalty.len() as u32 <= max_len_payout, "Market cannot payout to that many receivers");
+
+        let balance_u128: u128 = balance.into();
+
+        let mut payout: Payout = Payout { payout: HashMap::new() };
+        let mut total_perpetual = 0;
+
+        for (k, v) in royalty.iter() {
+            if *k != owner_id {
+                let key = k.clone();
+                payout.payout.insert(key, royalty_to_payout(*v, balance_u128));
+                total_perpetual += *v;
+            }
+        }
+        payout.payout.insert(owner_id, royalty_to_payout(10000 - total_perpetual, balance_u128));
+        payout
+    }
+
+    #[payable]
+    pub fn nft_transfer_payout(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        balance: Option<U128>,
+        max_len_payout: Option<u32>,
+    ) -> Option<Payout> {
+        assert_one_yocto();
+
+        let sender_id = env::predecessor_account_id();
+        // Transfer
+        let previous_token = self.nft_token(token_id.clone()).expect("no token");
+        self.internal_transfer_at_price(
+            &sender_id,
+            receiver_id.as_ref(),
+            &token_id,
+            approval_id,
+            None,
+            balance.map(|balance| balance.0),
+        );
+
+        // Payout calculation
+        let previous_owner_id = previous_token.owner_id;
+        let mut total_perpetual = 0;
+        let payout = if let Some(balance) = balance {
+            let balance_u128: u128 = u128::from(balance);
+            let mut payout: Payout = Payout { payout: HashMap::new() };
+
+            let royalty = self.internal_get_royalty(&token_id);
+
+            assert!(royalty.len() as u32 <= max_len_payout.unwrap(), "Market cannot payout to that many receivers");
+            for (k, v) in royalty.iter() {
+                let key = k.clone();
+                if key != previous_owner_id {
+                    payout.payout.insert(key, royalty_to_payout(*v, balance_u128));
+                    total_perpetual += *v;
+                }
+            }
+
+            assert!(
+                total_perpetual <= 10000,
+                "Total payout overflow"
+            );
+
+            payout.payout.insert(previous_owner_id.clone(), royalty_to_payout(10000 - total_perpetual, balance_u128));
+            Some(payout)
+        } else {
+            None
+        };
+
+        let authorized_id: Option<AccountId> = if sender_id != previous_owner_id {
+            Some(sender_id)
+        } else {
+            None
+        };
+
+        NearEvent::log_nft_transfer(
+            previous_owner_id,
+            receiver_id.to_string(),
+            vec![token_id],
+            None,
+            authorized_id,
+        );
+
+        payout
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.tokens.owner_id.clone()
+    }
+}
+
+fn token_series_to_json(token_series_id: TokenSeriesId, token_series: TokenSeries) -> TokenSeriesJson {
+    let minted = token_series.tokens.len();
+    TokenSeriesJson {
+        token_series_id,
+        remaining: token_series.metadata.copies.map(|copies| U64::from(copies.saturating_sub(minted))),
+        metadata: token_series.metadata,
+        creator_id: token_series.creator_id,
+        royalty: token_series.royalty,
+        royalty_locked: token_series.royalty_locked,
+        primary_split: token_series.primary_split,
+        price: token_series.price.map(U128::from),
+        is_mintable: token_series.is_mintable,
+        minted: U64::from(minted),
+        burned: U64::from(token_series.burned),
+        sale_starts_at: token_series.sale_starts_at.map(U64::from),
+        sale_ends_at: token_series.sale_ends_at.map(U64::from),
+        title_format: token_series.title_format,
+        transferability: token_series.transferability,
+        validity: token_series.validity,
+        metadata_frozen: token_series.metadata_frozen,
+    }
+}
+
+fn empty_token_metadata() -> TokenMetadata {
+    TokenMetadata {
+        title: None,
+        description: None,
+        media: None,
+        media_hash: None,
+        copies: None,
+        issued_at: None,
+        expires_at: None,
+        starts_at: None,
+        updated_at: None,
+        extra: None,
+        reference: None,
+        reference_hash: None,
+    }
+}
+
+/// Keeps a legacy per-token record as overrides only when it sets more than the mint timestamp
+fn compact_token_metadata(token_metadata: TokenMetadata) -> TokenData {
+    let issued_at = token_metadata.issued_at.as_ref().and_then(|issued_at| issued_at.parse().ok());
+    let has_overrides = token_metadata.title.is_some()
+        || token_metadata.description.is_some()
+        || token_metadata.media.is_some()
+        || token_metadata.media_hash.is_some()
+        || token_metadata.copies.is_some()
+        || token_metadata.expires_at.is_some()
+        || token_metadata.starts_at.is_some()
+        || token_metadata.updated_at.is_some()
+        || token_metadata.extra.is_some()
+        || token_metadata.reference.is_some()
+        || token_metadata.reference_hash.is_some()
+        || (token_metadata.issued_at.is_some() && issued_at.is_none());
+
+    TokenData {
+        issued_at,
+        overrides: if has_overrides { Some(token_metadata) } else { None },
+    }
+}
+
+/// Drops what a holder sets for the fields that identify the token or bound its validity,
+/// keeping `creator_set` values
+fn keep_creator_fields(metadata: TokenMetadata, creator_set: Option<&TokenMetadata>) -> TokenMetadata {
+    let creator_set = creator_set.cloned().unwrap_or_else(empty_token_metadata);
+    TokenMetadata {
+        title: creator_set.title,
+        media: creator_set.media,
+        media_hash: creator_set.media_hash,
+        starts_at: creator_set.starts_at,
+        expires_at: creator_set.expires_at,
+        reference: creator_set.reference,
+        reference_hash: creator_set.reference_hash,
+        ..metadata
+    }
+}
+
+/// Each field resolves to the token value, then the series value. Only the title is derived
+/// from the series title and edition, per `title_format`.
+fn inherit_metadata(
+    token_metadata: TokenMetadata,
+    series_metadata: TokenMetadata,
+    title_format: TitleFormat,
+    edition: &str,
+) -> TokenMetadata {
+    let title = token_metadata.title.or_else(|| {
+        series_metadata.title.map(|title| match (title_format, series_metadata.copies) {
+            (TitleFormat::Plain, _) => title,
+            (TitleFormat::EditionOfCopies, Some(copies)) => {
+                format!("{}{}{}{}{}", title, TITLE_DELIMETER, edition, EDITION_DELIMETER, copies)
+            }
+            _ => format!("{}{}{}", title, TITLE_DELIMETER, edition),
+        })
+    });
+
+    TokenMetadata {
+        title,
+        description: token_metadata.description.or(series_metadata.description),
+        media: token_metadata.media.or(series_metadata.media),
+        media_hash: token_metadata.media_hash.or(series_metadata.media_hash),
+        copies: token_metadata.copies.or(series_metadata.copies),
+        issued_at: token_metadata.issued_at.or(series_metadata.issued_at),
+        expires_at: token_metadata.expires_at.or(series_metadata.expires_at),
+        starts_at: token_metadata.starts_at.or(series_metadata.starts_at),
+        updated_at: token_metadata.updated_at.or(series_metadata.updated_at),
+        extra: token_metadata.extra.or(series_metadata.extra),
+        reference: token_metadata.reference.or(series_metadata.reference),
+        reference_hash: token_metadata.reference_hash.or(series_metadata.reference_hash),
+    }
+}
+
+/// Drops the lookahead item fetched past `limit` and returns the last item kept,
+/// whose id becomes the cursor for the next page
+fn page_cursor<T>(items: &mut Vec<T>, limit: usize) -> Option<&T> {
+    if items.len() > limit {
+        items.truncate(limit);
+        items.last()
+    } else {
+        None
+    }
+}
+
+fn get_token_series_id(token_id: &TokenId) -> TokenSeriesId {
+    token_id.split(TOKEN_DELIMETER).next().unwrap().to_string()
+}
+
+fn royalty_to_payout(a: u32, b: Balance) -> U128 {
+    U128(a as u128 * b / 10_000u128)
+}
+
+/// Empty split means the creator receives the whole primary sale
+fn assert_valid_primary_split(primary_split: &HashMap<AccountId, u32>) {
+    if primary_split.is_empty() {
+        return;
+    }
+
+    let mut total = 0;
+    for (k, v) in primary_split.iter() {
+        if !is_valid_account_id(k.as_bytes()) {
+            env::panic("Not valid account_id for primary split".as_bytes());
+        };
+        total += *v;
+    }
+
+    assert!(primary_split.len() <= 10, "Marble: primary split exceeds 10 accounts");
+
+    assert_eq!(total, 10000, "Marble: primary split must total 10000");
+}
+
+fn assert_sale_open(token_series: &TokenSeries) {
+    if let Some(sale_starts_at) = token_series.sale_starts_at {
+        assert!(env::block_timestamp() >= sale_starts_at, "Marble: sale has not started");
+    }
+    assert!(!is_sale_ended(token_series), "Marble: sale has ended");
+}
+
+fn is_sale_ended(token_series: &TokenSeries) -> bool {
+    token_series.sale_ends_at.map(|sale_ends_at| env::block_timestamp() >= sale_ends_at).unwrap_or(false)
+}
+
+/// Splits `amount` by `primary_split` basis points. Rounding dust goes to the last account
+/// (by account id order) so the parts always sum to `amount`.
+fn primary_sale_payout(token_series: &TokenSeries, amount: Balance) -> HashMap<AccountId, U128> {
+    let mut payout: HashMap<AccountId, U128> = HashMap::new();
+    if token_series.primary_split.is_empty() {
+        payout.insert(token_series.creator_id.clone(), U128(amount));
+        return payout;
+    }
+
+    let mut account_ids: Vec<&AccountId> = token_series.primary_split.keys().collect();
+    account_ids.sort();
+
+    let mut remaining = amount;
+    for (i, account_id) in account_ids.iter().enumerate() {
+        let share = if i == account_ids.len() - 1 {
+            remaining
+        } else {
+            royalty_to_payout(token_series.primary_split[*account_id], amount).0
+        };
+        remaining -= share;
+        payout.insert((*account_id).clone(), U128(share));
+    }
+    payout
+}
+
+fn assert_valid_royalty(royalty: &HashMap<AccountId, u32>) {
+    let mut total_perpetual = 0;
+    for (k, v) in royalty.iter() {
+        if !is_valid_account_id(k.as_bytes()) {
+            env::panic("Not valid account_id for royalty".as_bytes());
+        };
+        total_perpetual += *v;
+    }
+
+    assert!(royalty.len() <= 10, "Marble: royalty exceeds 10 accounts");
+
+    assert!(
+        total_perpetual <= 9000,
+        "Marble Exceeds maximum royalty -> 9000",
+    );
+}
+
+// near_contract_standards::impl_non_fungible_token_core!(Contract, tokens);
+// near_contract_standards::impl_non_fungible_token_enumeration!(Contract, tokens);
+#[near_bindgen]
+impl NonFungibleTokenMetadataProvider for Contract {
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        self.metadata.get().unwrap()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// `NonFungibleTokenResolver::nft_resolve_transfer` that also restores the approval terms
+    /// along with the approvals when the token comes back
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<HashMap<AccountId, u64>>,
+        approval_terms: Option<HashMap<AccountId, ApprovalTerms>>,
+    ) -> bool {
+        let resp: bool = self.tokens.nft_resolve_transfer(
+            previous_owner_id.clone(),
+            receiver_id.clone(),
+            token_id.clone(),
+            approved_account_ids,
+        );
+
+        // if not successful, return nft back to original owner
+        if !resp {
+            self.internal_on_owner_change(&token_id, Some(&receiver_id), Some(&previous_owner_id));
+            if let Some(approval_terms) = approval_terms {
+                self.approval_terms_by_id.insert(&token_id, &approval_terms);
+            }
+            NearEvent::log_nft_transfer(
+                receiver_id,
+                previous_owner_id,
+                vec![token_id],
+                None,
+                None,
+            );
+        }
+
+        resp
+    }
+}
+
+/// from https://github.com/near/near-sdk-rs/blob/e4abb739ff953b06d718037aa1b8ab768db17348/near-contract-standards/src/non_fungible_token/utils.rs#L29
+
+fn refund_deposit(storage_used: u64, extra_spend: Balance) {
+    let required_cost = env::storage_byte_cost() * Balance::from(storage_used);
+    let attached_deposit = env::attached_deposit() - extra_spend;
 
     assert!(
         required_cost <= attached_deposit,
@@ -1317,104 +2808,1040 @@ fn refund_deposit(storage_used: u64, extra_spend: Balance) {
         required_cost,
     );
 
-    let refund = attached_deposit - required_cost;
-    if refund > 1 {
-        Promise::new(env::predecessor_account_id()).transfer(refund);
+    let refund = attached_deposit - required_cost;
+    if refund > 1 {
+        Promise::new(env::predecessor_account_id()).transfer(refund);
+    }
+}
+
+/// Sends the storage released since `initial_storage_usage` to `account_id`
+fn refund_released_storage(account_id: &AccountId, initial_storage_usage: u64) {
+    let released = initial_storage_usage.saturating_sub(env::storage_usage());
+    if released > 0 {
+        Promise::new(account_id.clone()).transfer(env::storage_byte_cost() * Balance::from(released));
+    }
+}
+
+fn get_random_number(shift_amount: u32) -> u32 {
+    let mut seed = env::random_seed();
+    let seed_len = seed.len();
+    let mut arr: [u8; 4] = Default::default();
+    seed.rotate_left(shift_amount as usize % seed_len);
+    arr.copy_from_slice(&seed[..4]);
+    u32::from_le_bytes(arr)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::{testing_env};
+    use serde_with::with_prefix;
+
+    pub(crate) const STORAGE_FOR_CREATE_SERIES: Balance = 8540000000000000000000;
+    pub(crate) const STORAGE_FOR_MINT: Balance = 19000000000000000000000;
+
+    fn get_context(predecessor_account_id: ValidAccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(accounts(0))
+            .signer_account_id(predecessor_account_id.clone())
+            .predecessor_account_id(predecessor_account_id);
+        builder
+    }
+
+    pub(crate) fn setup_contract() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let contract = Contract::new_default_meta(accounts(1), accounts(4));
+        (context, contract)
+    }
+
+    #[test]
+    fn test_new() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+        let contract = Contract::new(
+            accounts(1),
+            accounts(4),
+            NFTContractMetadata {
+                spec: NFT_METADATA_SPEC.to_string(),
+                name: "Triple Triad".to_string(),
+                symbol: "TRIAD".to_string(),
+                icon: Some(DATA_IMAGE_SVG_COMIC_ICON.to_string()),
+                base_uri: Some("https://ipfs.fleek.co/ipfs/".to_string()),
+                reference: None,
+                reference_hash: None,
+            },
+        );
+        testing_env!(context.is_view(true).build());
+        assert_eq!(contract.get_owner(), accounts(1).to_string());
+        assert_eq!(contract.nft_metadata().base_uri.unwrap(), "https://ipfs.fleek.co/ipfs/".to_string());
+        assert_eq!(contract.nft_metadata().icon.unwrap(), DATA_IMAGE_SVG_COMIC_ICON.to_string());
+    }
+
+    pub(crate) fn create_series(
+        contract: &mut Contract,
+        royalty: &HashMap<AccountId, u32>,
+        price: Option<U128>,
+        copies: Option<u64>,
+    ) {
+        contract.nft_create_series(
+            TokenMetadata {
+                title: Some("Tsundere land".to_string()),
+                description: None,
+                media: Some(
+                    "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
+                ),
+                media_hash: None,
+                copies: copies,
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra: None,
+                reference: Some(
+                    "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+                ),
+                reference_hash: None,
+            },
+            price,
+            Some(royalty.clone()),
+            accounts(1),
+            None,
+            None,
+        );
+    }
+
+    /// Series "1" created by accounts(1) with a 10% royalty to itself
+    pub(crate) fn setup_series() -> (VMContextBuilder, Contract) {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        (context, contract)
+    }
+
+    /// Mints a token of `token_series_id` to `receiver_id` as accounts(1), the series creator
+    pub(crate) fn mint_token(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        token_series_id: &str,
+        receiver_id: ValidAccountId,
+    ) -> TokenId {
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_mint(token_series_id.to_string(), receiver_id, None, None, None)
+    }
+
+    /// `setup_series` plus a token of it held by accounts(2)
+    pub(crate) fn setup_token() -> (VMContextBuilder, Contract, TokenId) {
+        let (mut context, mut contract) = setup_series();
+        let token_id = mint_token(&mut context, &mut contract, "1", accounts(2));
+        (context, contract, token_id)
+    }
+
+    #[test]
+    fn test_create_series() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+        create_series(
+            &mut contract,
+            &royalty,
+            Some(U128::from(1 * 10u128.pow(24))),
+            None,
+        );
+
+        let nft_series_return = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(
+            nft_series_return.creator_id,
+            accounts(1).to_string()
+        );
+
+        assert_eq!(
+            nft_series_return.token_series_id,
+            "1",
+        );
+
+        assert_eq!(
+            nft_series_return.royalty,
+            royalty,
+        );
+
+        assert!(
+            nft_series_return.metadata.copies.is_none()
+        );
+
+        assert_eq!(
+            nft_series_return.metadata.title.unwrap(),
+            "Tsundere land".to_string()
+        );
+
+        assert_eq!(
+            nft_series_return.metadata.reference.unwrap(),
+            "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+        );
+    }
+
+    #[test]
+    fn test_buy() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+        let metadata = TokenMetadata {
+            title: Some("Tsundere land".to_string()),
+            description: None,
+            media: Some(
+                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
+            ),
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: Some(
+                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+            ),
+            reference_hash: None,
+        };
+        create_series(
+            &mut contract,
+            &royalty,
+            Some(U128::from(1 * 10u128.pow(24))),
+            None,
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1 * 10u128.pow(24) + STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let token_id = contract.nft_buy("1".to_string(), accounts(2), Some(metadata));
+
+        let token_from_nft_token = contract.nft_token(token_id);
+        assert_eq!(
+            token_from_nft_token.unwrap().owner_id,
+            accounts(2).to_string()
+        )
+    }
+
+    #[test]
+    fn test_holder_cannot_relabel_token() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        let mut metadata = empty_token_metadata();
+        metadata.title = Some("Rare gold edition".to_string());
+        metadata.media = Some("bafybeihnoou2av5w2bzmwkl6hi25scyzz6sjwdfqp4cwq2ikf6dfmev3ta".to_string());
+        metadata.description = Some("signed at the launch".to_string());
+        contract.nft_change_metadata(token_id.clone(), metadata);
+
+        let token_metadata = contract.nft_token(token_id).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.title, Some("Tsundere land #1".to_string()));
+        assert_eq!(token_metadata.media, contract.nft_get_series_single("1".to_string()).unwrap().metadata.media);
+        assert_eq!(token_metadata.description, Some("signed at the launch".to_string()));
+    }
+
+    #[test]
+    fn test_mint() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        let metadata = TokenMetadata {
+            title: Some("Tsundere land".to_string()),
+            description: None,
+            media: Some(
+                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
+            ),
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: Some(
+                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+            ),
+            reference_hash: None,
+        };
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+
+        let token_from_nft_token = contract.nft_token(token_id);
+        assert_eq!(
+            token_from_nft_token.unwrap().owner_id,
+            accounts(2).to_string()
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Token series is not mintable")]
+    fn test_invalid_mint_non_mintable() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_set_series_non_mintable("1".to_string());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Token series is not mintable")]
+    fn test_invalid_mint_above_copies() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, Some(1));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        let metadata = TokenMetadata {
+            title: Some("Tsundere land".to_string()),
+            description: None,
+            media: Some(
+                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
+            ),
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: Some(
+                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+            ),
+            reference_hash: None,
+        };
+        contract.nft_mint("1".to_string(), accounts(2), Some(metadata), None, None);
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+    }
+
+    #[test]
+    fn test_decrease_copies() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, Some(5));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        contract.nft_decrease_series_copies("1".to_string(), U64::from(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: cannot decrease supply, already minted : 2")]
+    fn test_invalid_decrease_copies() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, Some(5));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+
+        contract.nft_decrease_series_copies("1".to_string(), U64::from(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: not for sale")]
+    fn test_invalid_buy_price_null() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, Some(U128::from(1 * 10u128.pow(24))), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+
+        contract.nft_set_series_price("1".to_string(), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1 * 10u128.pow(24) + STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let token_id = contract.nft_buy("1".to_string(), accounts(2), None);
+
+        let token_from_nft_token = contract.nft_token(token_id);
+        assert_eq!(
+            token_from_nft_token.unwrap().owner_id,
+            accounts(2).to_string()
+        )
+    }
+
+    #[test]
+    fn test_nft_burn() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let metadata = TokenMetadata {
+            title: Some("Tsundere land".to_string()),
+            description: None,
+            media: Some(
+                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
+            ),
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: Some(
+                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+            ),
+            reference_hash: None,
+        };
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), Some(metadata), None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+
+        contract.nft_burn(token_id.clone());
+        let token = contract.nft_token(token_id);
+        assert!(token.is_none());
+    }
+
+    #[test]
+    fn test_nft_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let metadata = TokenMetadata {
+            title: Some("Tsundere land".to_string()),
+            description: None,
+            media: Some(
+                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
+            ),
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: Some(
+                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+            ),
+            reference_hash: None,
+        };
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), Some(metadata), None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+
+        contract.nft_transfer(accounts(3), token_id.clone(), None, None);
+
+        let token = contract.nft_token(token_id).unwrap();
+        assert_eq!(
+            token.owner_id,
+            accounts(3).to_string()
+        )
+    }
+
+    #[test]
+    fn test_nft_transfer_payout() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+
+        let payout = contract.nft_transfer_payout(
+            accounts(3),
+            token_id.clone(),
+            Some(0),
+            Some(U128::from(1 * 10u128.pow(24))),
+            Some(10),
+        );
+
+        let mut payout_calc: HashMap<AccountId, U128> = HashMap::new();
+        payout_calc.insert(
+            accounts(1).to_string(),
+            U128::from((1000 * (1 * 10u128.pow(24))) / 10_000),
+        );
+        payout_calc.insert(
+            accounts(2).to_string(),
+            U128::from((9000 * (1 * 10u128.pow(24))) / 10_000),
+        );
+
+        assert_eq!(payout.unwrap().payout, payout_calc);
+
+        let token = contract.nft_token(token_id).unwrap();
+        assert_eq!(
+            token.owner_id,
+            accounts(3).to_string()
+        )
+    }
+
+    #[test]
+    fn test_create_mint_bundle() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        contract.create_mint_bundle(
+            "test-bundle-test".to_string(),
+            Some(vec!["1".to_string(), "2".to_string()]),
+            None,
+            Some(U128::from(5 * 10u128.pow(24))),
+            None
+        );
+
+        let mint_bundle = contract.get_mint_bundle("test-bundle-test".to_string());
+
+        assert_eq!(mint_bundle.token_series_ids, Some(vec!["1".to_string(), "2".to_string()]));
+        assert_eq!(mint_bundle.token_ids, None);
+        assert_eq!(mint_bundle.price.unwrap(), U128::from(5 * 10u128.pow(24)));
+        assert_eq!(mint_bundle.limit_buy, None);
+    }
+
+    #[test]
+    fn test_buy_mint_bundle() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, Some(2));
+        create_series(&mut contract, &royalty, None, Some(2));
+        create_series(&mut contract, &royalty, None, Some(2));
+        create_series(&mut contract, &royalty, None, Some(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let price = 5 * 10u128.pow(24);
+        let mint_bundle_id = "test-bundle-test".to_string();
+        contract.create_mint_bundle(
+            mint_bundle_id.clone(),
+            Some(vec!["1".to_string(), "2".to_string(), "3".to_string(), "4".to_string()]),
+            None,
+            Some(U128::from(price)),
+            None
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(price + STORAGE_FOR_MINT)
+            .build()
+        );
+
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Mint bundle does not exist or already finished")]
+    fn test_invalid_exhaust_mint_bundle() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, Some(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let price = 5 * 10u128.pow(24);
+        let mint_bundle_id = "test-bundle-test".to_string();
+        contract.create_mint_bundle(
+            mint_bundle_id.clone(),
+            Some(vec!["1".to_string()]),
+            None,
+            Some(U128::from(price)),
+            None
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(price + STORAGE_FOR_MINT)
+            .build()
+        );
+
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Mint exhausted for account_id charlie")]
+    fn test_invalid_exhaust_limit_buy_mint_bundle() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, Some(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let price = 5 * 10u128.pow(24);
+        let mint_bundle_id = "test-bundle-test".to_string();
+        contract.create_mint_bundle(
+            mint_bundle_id.clone(),
+            Some(vec!["1".to_string()]),
+            None,
+            Some(U128::from(price)),
+            Some(1)
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(price + STORAGE_FOR_MINT)
+            .build()
+        );
+
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
+        contract.buy_mint_bundle(mint_bundle_id, accounts(2));
     }
-}
 
-fn get_random_number(shift_amount: u32) -> u32 {
-    let mut seed = env::random_seed();
-    let seed_len = seed.len();
-    let mut arr: [u8; 4] = Default::default();
-    seed.rotate_left(shift_amount as usize % seed_len);
-    arr.copy_from_slice(&seed[..4]);
-    u32::from_le_bytes(arr)
-}
+    #[test]
+    fn test_set_series_royalty() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
 
-#[cfg(all(test, not(target_arch = "wasm32")))]
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env};
-    use serde_with::with_prefix;
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
 
-    const STORAGE_FOR_CREATE_SERIES: Balance = 8540000000000000000000;
-    const STORAGE_FOR_MINT: Balance = 11280000000000000000000;
+        create_series(&mut contract, &royalty, None, None);
 
-    fn get_context(predecessor_account_id: ValidAccountId) -> VMContextBuilder {
-        let mut builder = VMContextBuilder::new();
-        builder
-            .current_account_id(accounts(0))
-            .signer_account_id(predecessor_account_id.clone())
-            .predecessor_account_id(predecessor_account_id);
-        builder
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut new_royalty: HashMap<AccountId, u32> = HashMap::new();
+        new_royalty.insert(accounts(3).to_string(), 500);
+        new_royalty.insert(accounts(4).to_string(), 500);
+        contract.nft_set_series_royalty("1".to_string(), new_royalty.clone());
+
+        let nft_series_return = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(nft_series_return.royalty, new_royalty);
     }
 
-    fn setup_contract() -> (VMContextBuilder, Contract) {
-        let mut context = VMContextBuilder::new();
-        testing_env!(context.predecessor_account_id(accounts(0)).build());
-        let contract = Contract::new_default_meta(accounts(1), accounts(4));
-        (context, contract)
+    #[test]
+    #[should_panic(expected = "Marble: royalty is locked")]
+    fn test_invalid_set_series_royalty_locked() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+
+        contract.nft_lock_series_royalty("1".to_string());
+
+        royalty.insert(accounts(3).to_string(), 1000);
+        contract.nft_set_series_royalty("1".to_string(), royalty);
     }
 
     #[test]
-    fn test_new() {
-        let mut context = get_context(accounts(1));
-        testing_env!(context.build());
-        let contract = Contract::new(
-            accounts(1),
-            accounts(4),
-            NFTContractMetadata {
-                spec: NFT_METADATA_SPEC.to_string(),
-                name: "Triple Triad".to_string(),
-                symbol: "TRIAD".to_string(),
-                icon: Some(DATA_IMAGE_SVG_COMIC_ICON.to_string()),
-                base_uri: Some("https://ipfs.fleek.co/ipfs/".to_string()),
-                reference: None,
-                reference_hash: None,
-            },
+    #[should_panic(expected = "Marble Exceeds maximum royalty -> 9000")]
+    fn test_invalid_set_series_royalty_above_max() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
         );
-        testing_env!(context.is_view(true).build());
-        assert_eq!(contract.get_owner(), accounts(1).to_string());
-        assert_eq!(contract.nft_metadata().base_uri.unwrap(), "https://ipfs.fleek.co/ipfs/".to_string());
-        assert_eq!(contract.nft_metadata().icon.unwrap(), DATA_IMAGE_SVG_COMIC_ICON.to_string());
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+
+        royalty.insert(accounts(3).to_string(), 8500);
+        contract.nft_set_series_royalty("1".to_string(), royalty);
     }
 
-    fn create_series(
-        contract: &mut Contract,
-        royalty: &HashMap<AccountId, u32>,
-        price: Option<U128>,
-        copies: Option<u64>,
-    ) {
-        contract.nft_create_series(
-            TokenMetadata {
-                title: Some("Tsundere land".to_string()),
-                description: None,
-                media: Some(
-                    "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
-                ),
-                media_hash: None,
-                copies: copies,
-                issued_at: None,
-                expires_at: None,
-                starts_at: None,
-                updated_at: None,
-                extra: None,
-                reference: Some(
-                    "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
-                ),
-                reference_hash: None,
-            },
-            price,
-            Some(royalty.clone()),
-            accounts(1),
+    #[test]
+    fn test_nft_transfer_payout_token_royalty() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let mut token_royalty: HashMap<AccountId, u32> = HashMap::new();
+        token_royalty.insert(accounts(1).to_string(), 500);
+        token_royalty.insert(accounts(4).to_string(), 1500);
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, Some(token_royalty), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+
+        let payout = contract.nft_transfer_payout(
+            accounts(3),
+            token_id,
+            Some(0),
+            Some(U128::from(1 * 10u128.pow(24))),
+            Some(10),
+        );
+
+        let mut payout_calc: HashMap<AccountId, U128> = HashMap::new();
+        payout_calc.insert(
+            accounts(1).to_string(),
+            U128::from((500 * (1 * 10u128.pow(24))) / 10_000),
+        );
+        payout_calc.insert(
+            accounts(4).to_string(),
+            U128::from((1500 * (1 * 10u128.pow(24))) / 10_000),
+        );
+        payout_calc.insert(
+            accounts(2).to_string(),
+            U128::from((8000 * (1 * 10u128.pow(24))) / 10_000),
+        );
+
+        assert_eq!(payout.unwrap().payout, payout_calc);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: royalty exceeds 10 accounts")]
+    fn test_invalid_mint_token_royalty_too_many_accounts() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let mut token_royalty: HashMap<AccountId, u32> = HashMap::new();
+        for i in 0..11 {
+            token_royalty.insert(format!("collaborator{}", i), 100);
+        }
+        contract.nft_mint("1".to_string(), accounts(2), None, Some(token_royalty), None);
+    }
+
+    #[test]
+    fn test_transfer_series_creator() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+        royalty.insert(accounts(3).to_string(), 500);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        contract.nft_propose_series_creator("1".to_string(), accounts(2), Some(true));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+
+        contract.nft_accept_series_creator("1".to_string());
+
+        let nft_series_return = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(nft_series_return.creator_id, accounts(2).to_string());
+
+        let mut royalty_calc: HashMap<AccountId, u32> = HashMap::new();
+        royalty_calc.insert(accounts(2).to_string(), 1000);
+        royalty_calc.insert(accounts(3).to_string(), 500);
+        assert_eq!(nft_series_return.royalty, royalty_calc);
+        assert!(contract.nft_get_series_creator_proposal("1".to_string()).is_none());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
         );
+        contract.nft_mint("1".to_string(), accounts(3), None, None, None);
     }
 
     #[test]
-    fn test_create_series() {
+    #[should_panic(expected = "Marble: Proposed creator only")]
+    fn test_invalid_accept_series_creator() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1424,46 +3851,50 @@ mod tests {
 
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
-        create_series(
-            &mut contract,
-            &royalty,
-            Some(U128::from(1 * 10u128.pow(24))),
-            None,
-        );
 
-        let nft_series_return = contract.nft_get_series_single("1".to_string());
-        assert_eq!(
-            nft_series_return.creator_id,
-            accounts(1).to_string()
-        );
+        create_series(&mut contract, &royalty, None, None);
 
-        assert_eq!(
-            nft_series_return.token_series_id,
-            "1",
-        );
+        contract.nft_propose_series_creator("1".to_string(), accounts(2), None);
 
-        assert_eq!(
-            nft_series_return.royalty,
-            royalty,
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
         );
 
-        assert!(
-            nft_series_return.metadata.copies.is_none()
-        );
+        contract.nft_accept_series_creator("1".to_string());
+    }
 
-        assert_eq!(
-            nft_series_return.metadata.title.unwrap(),
-            "Tsundere land".to_string()
+    #[test]
+    fn test_force_series_creator() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
         );
 
-        assert_eq!(
-            nft_series_return.metadata.reference.unwrap(),
-            "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
         );
+
+        // accounts(1) is also the contract owner in setup_contract
+        contract.nft_force_series_creator("1".to_string(), accounts(3), None);
+
+        let nft_series_return = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(nft_series_return.creator_id, accounts(3).to_string());
+        assert_eq!(nft_series_return.royalty, royalty);
     }
 
     #[test]
-    fn test_buy() {
+    fn test_transfer_series_creator_moves_primary_split() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1473,48 +3904,31 @@ mod tests {
 
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
-        let metadata = TokenMetadata {
-            title: Some("Tsundere land".to_string()),
-            description: None,
-            media: Some(
-                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
-            ),
-            media_hash: None,
-            copies: None,
-            issued_at: None,
-            expires_at: None,
-            starts_at: None,
-            updated_at: None,
-            extra: None,
-            reference: Some(
-                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
-            ),
-            reference_hash: None,
-        };
-        create_series(
-            &mut contract,
-            &royalty,
-            Some(U128::from(1 * 10u128.pow(24))),
-            None,
-        );
+
+        create_series(&mut contract, &royalty, None, None);
+
+        let mut primary_split: HashMap<AccountId, u32> = HashMap::new();
+        primary_split.insert(accounts(1).to_string(), 6000);
+        primary_split.insert(accounts(4).to_string(), 4000);
+        contract.nft_set_series_primary_split("1".to_string(), primary_split);
+        contract.nft_propose_series_creator("1".to_string(), accounts(2), None);
 
         testing_env!(context
             .predecessor_account_id(accounts(2))
-            .attached_deposit(1 * 10u128.pow(24) + STORAGE_FOR_MINT)
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_accept_series_creator("1".to_string());
 
-        let token_id = contract.nft_buy("1".to_string(), accounts(2), Some(metadata));
-
-        let token_from_nft_token = contract.nft_token(token_id);
-        assert_eq!(
-            token_from_nft_token.unwrap().owner_id,
-            accounts(2).to_string()
-        )
+        let mut primary_split_calc: HashMap<AccountId, u32> = HashMap::new();
+        primary_split_calc.insert(accounts(2).to_string(), 6000);
+        primary_split_calc.insert(accounts(4).to_string(), 4000);
+        let nft_series_return = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(nft_series_return.primary_split, primary_split_calc);
     }
 
     #[test]
-    fn test_mint() {
+    fn test_primary_sale_payout_sums_to_price() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1527,41 +3941,42 @@ mod tests {
 
         create_series(&mut contract, &royalty, None, None);
 
+        let mut primary_split: HashMap<AccountId, u32> = HashMap::new();
+        primary_split.insert(accounts(1).to_string(), 3333);
+        primary_split.insert(accounts(2).to_string(), 3333);
+        primary_split.insert(accounts(3).to_string(), 3334);
+        contract.nft_set_series_primary_split("1".to_string(), primary_split.clone());
+
+        let token_series = contract.token_series_by_id.get(&"1".to_string()).unwrap();
+        assert_eq!(token_series.primary_split, primary_split);
+
+        let price: Balance = 1 * 10u128.pow(24) + 7;
+        let for_treasury = price * TREASURY_FEE / 10_000u128;
+        let payout = primary_sale_payout(&token_series, price - for_treasury);
+
+        assert_eq!(payout.len(), 3);
+        let total: Balance = payout.values().map(|amount| amount.0).sum();
+        assert_eq!(total + for_treasury, price);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: series already has minted tokens")]
+    fn test_invalid_primary_split_after_mint() {
+        let (mut context, mut contract, _) = setup_token();
+
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_MINT)
+            .attached_deposit(1)
             .build()
         );
-        let metadata = TokenMetadata {
-            title: Some("Tsundere land".to_string()),
-            description: None,
-            media: Some(
-                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
-            ),
-            media_hash: None,
-            copies: None,
-            issued_at: None,
-            expires_at: None,
-            starts_at: None,
-            updated_at: None,
-            extra: None,
-            reference: Some(
-                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
-            ),
-            reference_hash: None,
-        };
-        let token_id = contract.nft_mint("1".to_string(), accounts(2), None);
-
-        let token_from_nft_token = contract.nft_token(token_id);
-        assert_eq!(
-            token_from_nft_token.unwrap().owner_id,
-            accounts(2).to_string()
-        )
+        let mut primary_split: HashMap<AccountId, u32> = HashMap::new();
+        primary_split.insert(accounts(1).to_string(), 10000);
+        contract.nft_set_series_primary_split("1".to_string(), primary_split);
     }
 
     #[test]
-    #[should_panic(expected = "Marble: Token series is not mintable")]
-    fn test_invalid_mint_non_mintable() {
+    #[should_panic(expected = "Marble: primary split must total 10000")]
+    fn test_invalid_primary_split_total() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1574,25 +3989,52 @@ mod tests {
 
         create_series(&mut contract, &royalty, None, None);
 
+        let mut primary_split: HashMap<AccountId, u32> = HashMap::new();
+        primary_split.insert(accounts(1).to_string(), 5000);
+        primary_split.insert(accounts(2).to_string(), 4000);
+        contract.nft_set_series_primary_split("1".to_string(), primary_split);
+    }
+
+    #[test]
+    fn test_get_series_by_creator() {
+        let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(1)
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
             .build()
         );
-        contract.nft_set_series_non_mintable("1".to_string());
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+        create_series(&mut contract, &royalty, None, None);
+        create_series(&mut contract, &royalty, None, None);
+
+        assert_eq!(contract.nft_series_count_for_creator(accounts(1)), U64::from(3));
+        let series = contract.nft_get_series_by_creator(accounts(1), Some(U128::from(1)), Some(5));
+        assert_eq!(
+            series.iter().map(|s| s.token_series_id.clone()).collect::<Vec<TokenSeriesId>>(),
+            vec!["2".to_string(), "3".to_string()]
+        );
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_MINT)
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_force_series_creator("2".to_string(), accounts(3), None);
 
-        contract.nft_mint("1".to_string(), accounts(2), None);
+        assert_eq!(contract.nft_series_count_for_creator(accounts(1)), U64::from(2));
+        assert_eq!(contract.nft_series_count_for_creator(accounts(3)), U64::from(1));
+        assert_eq!(
+            contract.nft_get_series_by_creator(accounts(3), None, None)[0].token_series_id,
+            "2".to_string()
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Marble: Token series is not mintable")]
-    fn test_invalid_mint_above_copies() {
+    fn test_get_series_supply_and_sale_state() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1603,37 +4045,38 @@ mod tests {
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
 
-        create_series(&mut contract, &royalty, None, Some(1));
+        create_series(&mut contract, &royalty, Some(U128::from(1 * 10u128.pow(24))), Some(5));
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
             .attached_deposit(STORAGE_FOR_MINT)
             .build()
         );
-        let metadata = TokenMetadata {
-            title: Some("Tsundere land".to_string()),
-            description: None,
-            media: Some(
-                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
-            ),
-            media_hash: None,
-            copies: None,
-            issued_at: None,
-            expires_at: None,
-            starts_at: None,
-            updated_at: None,
-            extra: None,
-            reference: Some(
-                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
-            ),
-            reference_hash: None,
-        };
-        contract.nft_mint("1".to_string(), accounts(2),Some(metadata));
-        contract.nft_mint("1".to_string(), accounts(2), None);
+
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_burn(token_id);
+
+        let nft_series_return = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(nft_series_return.price, Some(U128::from(1 * 10u128.pow(24))));
+        assert!(nft_series_return.is_mintable);
+        assert_eq!(nft_series_return.minted, U64::from(2));
+        assert_eq!(nft_series_return.burned, U64::from(1));
+        assert_eq!(nft_series_return.remaining, Some(U64::from(3)));
+
+        assert!(contract.nft_get_series_single("2".to_string()).is_none());
+        assert!(contract.nft_get_series_price("2".to_string()).is_none());
     }
 
     #[test]
-    fn test_decrease_copies() {
+    #[should_panic(expected = "Marble: sale has not started")]
+    fn test_invalid_buy_before_sale_window() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1644,29 +4087,30 @@ mod tests {
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
 
-        create_series(&mut contract, &royalty, None, Some(5));
+        create_series(&mut contract, &royalty, Some(U128::from(1 * 10u128.pow(24))), None);
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_MINT)
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_set_series_sale_window("1".to_string(), Some(U64::from(1_000)), None);
 
-        contract.nft_mint("1".to_string(), accounts(2), None);
-        contract.nft_mint("1".to_string(), accounts(2), None);
+        let nft_series_return = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(nft_series_return.sale_starts_at, Some(U64::from(1_000)));
 
         testing_env!(context
-            .predecessor_account_id(accounts(1))
-            .attached_deposit(1)
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(999)
+            .attached_deposit(1 * 10u128.pow(24) + STORAGE_FOR_MINT)
             .build()
         );
 
-        contract.nft_decrease_series_copies("1".to_string(), U64::from(3));
+        contract.nft_buy("1".to_string(), accounts(2), None);
     }
 
     #[test]
-    #[should_panic(expected = "Marble: cannot decrease supply, already minted : 2")]
-    fn test_invalid_decrease_copies() {
+    fn test_buy_mint_bundle_skips_ended_series() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1677,29 +4121,57 @@ mod tests {
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
 
-        create_series(&mut contract, &royalty, None, Some(5));
+        create_series(&mut contract, &royalty, None, Some(2));
+        create_series(&mut contract, &royalty, None, Some(2));
+
+        let price = 5 * 10u128.pow(24);
+        let mint_bundle_id = "test-bundle-test".to_string();
+        contract.create_mint_bundle(
+            mint_bundle_id.clone(),
+            Some(vec!["1".to_string(), "2".to_string()]),
+            None,
+            Some(U128::from(price)),
+            None
+        );
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_MINT)
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_set_series_sale_window("2".to_string(), None, Some(U64::from(1_000)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(1_000)
+            .attached_deposit(price + STORAGE_FOR_MINT)
             .build()
         );
 
-        contract.nft_mint("1".to_string(), accounts(2), None);
-        contract.nft_mint("1".to_string(), accounts(2), None);
+        let token_id = contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2)).unwrap();
+        assert_eq!(get_token_series_id(&token_id), "1".to_string());
 
+        // once every series in the bundle has ended the bundle is closed and the deposit returned
         testing_env!(context
             .predecessor_account_id(accounts(1))
+            .block_timestamp(0)
             .attached_deposit(1)
             .build()
         );
+        contract.nft_set_series_sale_window("1".to_string(), None, Some(U64::from(1_000)));
 
-        contract.nft_decrease_series_copies("1".to_string(), U64::from(4));
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .block_timestamp(1_000)
+            .attached_deposit(price + STORAGE_FOR_MINT)
+            .build()
+        );
+        assert!(contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2)).is_none());
+        assert!(contract.mint_bundles.get(&mint_bundle_id).is_none());
     }
 
     #[test]
-    #[should_panic(expected = "Marble: not for sale")]
-    fn test_invalid_buy_price_null() {
+    fn test_cursor_pagination() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1710,33 +4182,122 @@ mod tests {
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
 
-        create_series(&mut contract, &royalty, Some(U128::from(1 * 10u128.pow(24))), None);
+        create_series(&mut contract, &royalty, None, None);
+        create_series(&mut contract, &royalty, None, None);
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        for _ in 0..5 {
+            contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        }
+
+        let series_page = contract.nft_get_series_cursor(None, Some(2));
+        assert_eq!(series_page.series.len(), 2);
+        assert_eq!(series_page.next_cursor, Some("2".to_string()));
+        let series_page = contract.nft_get_series_cursor(series_page.next_cursor, Some(2));
+        assert_eq!(series_page.series[0].token_series_id, "3".to_string());
+        assert_eq!(series_page.next_cursor, None);
+
+        let page = contract.nft_tokens_by_series_cursor("1".to_string(), None, Some(3));
+        assert_eq!(page.tokens.len(), 3);
+        assert_eq!(page.next_cursor, Some("1:3".to_string()));
+        let page = contract.nft_tokens_by_series_cursor("1".to_string(), page.next_cursor, Some(3));
+        assert_eq!(page.tokens.len(), 2);
+        assert_eq!(page.tokens[0].token_id, "1:4".to_string());
+        assert_eq!(page.next_cursor, None);
+
+        let page = contract.nft_tokens_cursor(None, Some(4));
+        assert_eq!(page.tokens.len(), 4);
+        let page = contract.nft_tokens_cursor(page.next_cursor, Some(4));
+        assert_eq!(page.tokens.len(), 1);
+        assert_eq!(page.next_cursor, None);
+
+        let page = contract.nft_tokens_for_owner_cursor(accounts(2), Some("1:3".to_string()), Some(10));
+        assert_eq!(page.tokens.len(), 2);
+        assert_eq!(page.next_cursor, None);
+
+        assert!(contract.nft_tokens(None, Some(0)).is_empty());
+        assert!(contract.nft_tokens_for_owner(accounts(2), None, Some(0)).is_empty());
+
+        // past the end returns empty instead of panicking
+        assert!(contract.nft_tokens(Some(U128::from(10)), None).is_empty());
+        assert!(contract.nft_get_series(Some(U128::from(10)), None).is_empty());
+        assert!(contract.nft_tokens_by_series("1".to_string(), Some(U128::from(10)), None).is_empty());
+        assert!(contract.nft_tokens_by_series("9".to_string(), None, None).is_empty());
+        assert!(contract.nft_tokens_for_owner(accounts(2), Some(U128::from(10)), None).is_empty());
+        assert!(contract.nft_tokens_for_owner_cursor(accounts(2), Some("1:9".to_string()), None).tokens.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_token_series_is_idempotent() {
+        let (mut context, mut contract) = setup_contract();
+
+        // series "1" still in the layout from before the upgrade
+        let mut prev_token_series_by_id: UnorderedMap<TokenSeriesId, TokenSeriesV1> =
+            UnorderedMap::new(StorageKey::TokenSeriesById);
+        prev_token_series_by_id.insert(&"1".to_string(), &TokenSeriesV1 {
+            metadata: TokenMetadata {
+                title: Some("Tsundere land".to_string()),
+                description: None,
+                media: None,
+                media_hash: None,
+                copies: None,
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra: None,
+                reference: None,
+                reference_hash: None,
+            },
+            creator_id: accounts(1).to_string(),
+            tokens: UnorderedSet::new(StorageKey::TokensBySeriesInner {
+                token_series: "1".to_string(),
+            }),
+            price: None,
+            is_mintable: true,
+            royalty: HashMap::new(),
+        });
+        contract.token_series_by_id = UnorderedMap::try_from_slice(&prev_token_series_by_id.try_to_vec().unwrap()).unwrap();
+        contract.series_migration_pending = true;
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(1)
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
             .build()
         );
+        create_series(&mut contract, &HashMap::new(), None, None);
 
-        contract.nft_set_series_price("1".to_string(), None);
+        assert_eq!(contract.migrate_token_series(U64::from(0), U64::from(1)), U64::from(1));
+        assert!(contract.series_migration_pending);
+        assert_eq!(contract.migrate_token_series(U64::from(0), U64::from(10)), U64::from(2));
+        assert!(!contract.series_migration_pending);
 
-        testing_env!(context
-            .predecessor_account_id(accounts(2))
-            .attached_deposit(1 * 10u128.pow(24) + STORAGE_FOR_MINT)
-            .build()
-        );
+        let token_series = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(token_series.title_format, TitleFormat::Edition);
+        assert_eq!(contract.nft_get_series_single("2".to_string()).unwrap().creator_id, accounts(1).to_string());
 
-        let token_id = contract.nft_buy("1".to_string(), accounts(2), None);
+        contract.migrate_series_by_creator(U64::from(0), U64::from(10));
+        assert_eq!(contract.nft_get_series_by_creator(accounts(1), None, None).len(), 2);
+    }
 
-        let token_from_nft_token = contract.nft_token(token_id);
-        assert_eq!(
-            token_from_nft_token.unwrap().owner_id,
-            accounts(2).to_string()
-        )
+    #[test]
+    #[should_panic(expected = "Marble: run migrate_token_series first")]
+    fn test_invalid_migrate_series_by_creator_before_token_series() {
+        let (mut context, mut contract) = setup_contract();
+        contract.series_migration_pending = true;
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.migrate_series_by_creator(U64::from(0), U64::from(10));
     }
 
     #[test]
-    fn test_nft_burn() {
+    fn test_migrate_owners_by_series_is_idempotent() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1754,95 +4315,88 @@ mod tests {
             .attached_deposit(STORAGE_FOR_MINT)
             .build()
         );
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        contract.nft_mint("1".to_string(), accounts(3), None, None, None);
 
-        let metadata = TokenMetadata {
-            title: Some("Tsundere land".to_string()),
-            description: None,
-            media: Some(
-                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
-            ),
-            media_hash: None,
-            copies: None,
-            issued_at: None,
-            expires_at: None,
-            starts_at: None,
-            updated_at: None,
-            extra: None,
-            reference: Some(
-                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
-            ),
-            reference_hash: None,
-        };
-        let token_id = contract.nft_mint("1".to_string(), accounts(2), Some(metadata));
+        contract.owners_backfill_pending = true;
+        assert_eq!(contract.migrate_owners_by_series(None, U64::from(1)), Some("1:1".to_string()));
+        assert_eq!(contract.migrate_owners_by_series(None, U64::from(10)), None);
+        assert_eq!(contract.migrate_owners_by_series(None, U64::from(10)), None);
+
+        assert_eq!(contract.nft_balance_of_series(accounts(2), "1".to_string()), U64::from(1));
+        assert_eq!(contract.nft_balance_of_series(accounts(3), "1".to_string()), U64::from(1));
+        assert!(!contract.owners_backfill_pending);
+    }
+
+    #[test]
+    fn test_transfer_during_owners_backfill() {
+        let (mut context, mut contract, token_id) = setup_token();
+        mint_token(&mut context, &mut contract, "1", accounts(2));
+
+        // both tokens were minted before the owner indexes existed
+        let mut owners = contract.owners_by_series.get(&"1".to_string()).unwrap();
+        owners.clear();
+        contract.owners_by_series.remove(&"1".to_string());
+        let mut token_ids = contract.token_ids_by_owner.get(&accounts(2).to_string()).unwrap();
+        token_ids.clear();
+        contract.token_ids_by_owner.remove(&accounts(2).to_string());
+        contract.owners_backfill_pending = true;
 
         testing_env!(context
             .predecessor_account_id(accounts(2))
             .attached_deposit(1)
             .build()
         );
+        contract.nft_transfer(accounts(3), token_id.clone(), None, None);
 
-        contract.nft_burn(token_id.clone());
-        let token = contract.nft_token(token_id);
-        assert!(token.is_none());
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.migrate_owners_by_series(None, U64::from(10)), None);
+
+        assert_eq!(contract.nft_balance_of_series(accounts(2), "1".to_string()), U64::from(1));
+        assert_eq!(contract.nft_balance_of_series(accounts(3), "1".to_string()), U64::from(1));
+        let page = contract.nft_tokens_for_owner_cursor(accounts(3), None, None);
+        assert_eq!(page.tokens[0].token_id, token_id);
+        assert_eq!(contract.nft_tokens_for_owner_cursor(accounts(2), None, None).tokens.len(), 1);
     }
 
     #[test]
-    fn test_nft_transfer() {
+    #[should_panic(expected = "Marble: limit must be greater than 0")]
+    fn test_invalid_migrate_owners_by_series_zero_limit() {
         let (mut context, mut contract) = setup_contract();
-        testing_env!(context
-            .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
-            .build()
-        );
+        contract.owners_backfill_pending = true;
 
-        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
-        royalty.insert(accounts(1).to_string(), 1000);
-
-        create_series(&mut contract, &royalty, None, None);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.migrate_owners_by_series(None, U64::from(0));
+    }
 
-        testing_env!(context
-            .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_MINT)
-            .build()
-        );
+    #[test]
+    fn test_series_cursor_scan_is_bounded() {
+        let (mut context, mut contract) = setup_series();
 
-        let metadata = TokenMetadata {
-            title: Some("Tsundere land".to_string()),
-            description: None,
-            media: Some(
-                "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
-            ),
-            media_hash: None,
-            copies: None,
-            issued_at: None,
-            expires_at: None,
-            starts_at: None,
-            updated_at: None,
-            extra: None,
-            reference: Some(
-                "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
-            ),
-            reference_hash: None,
-        };
-        let token_id = contract.nft_mint("1".to_string(), accounts(2), Some(metadata));
+        let token_ids: Vec<TokenId> = (0..MAX_EDITIONS_SCANNED + 2)
+            .map(|_| mint_token(&mut context, &mut contract, "1", accounts(2)))
+            .collect();
 
         testing_env!(context
             .predecessor_account_id(accounts(2))
             .attached_deposit(1)
             .build()
         );
+        for token_id in token_ids.iter().take(MAX_EDITIONS_SCANNED as usize) {
+            contract.nft_burn(token_id.clone());
+        }
 
-        contract.nft_transfer(accounts(3), token_id.clone(), None, None);
+        let page = contract.nft_tokens_by_series_cursor("1".to_string(), None, Some(1));
+        assert!(page.tokens.is_empty());
+        assert_eq!(page.next_cursor, Some(format!("1:{}", MAX_EDITIONS_SCANNED)));
 
-        let token = contract.nft_token(token_id).unwrap();
-        assert_eq!(
-            token.owner_id,
-            accounts(3).to_string()
-        )
+        let page = contract.nft_tokens_by_series_cursor("1".to_string(), page.next_cursor, Some(1));
+        assert_eq!(page.tokens[0].token_id, format!("1:{}", MAX_EDITIONS_SCANNED + 1));
+        assert_eq!(page.next_cursor, Some(format!("1:{}", MAX_EDITIONS_SCANNED + 1)));
     }
 
     #[test]
-    fn test_nft_transfer_unsafe() {
+    fn test_owner_cursor_stable_across_transfer() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1861,24 +4415,29 @@ mod tests {
             .build()
         );
 
-        let token_id = contract.nft_mint("1".to_string(), accounts(2), None);
+        for _ in 0..5 {
+            contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        }
+
+        let page = contract.nft_tokens_for_owner_cursor(accounts(2), None, Some(2));
+        assert_eq!(page.next_cursor, Some("1:2".to_string()));
 
+        // the owner set moves its last token into the freed slot
         testing_env!(context
             .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_transfer(accounts(3), "1:1".to_string(), None, None);
 
-        contract.nft_transfer_unsafe(accounts(3), token_id.clone(), None, None);
-
-        let token = contract.nft_token(token_id).unwrap();
-        assert_eq!(
-            token.owner_id,
-            accounts(3).to_string()
-        )
+        let page = contract.nft_tokens_for_owner_cursor(accounts(2), page.next_cursor, Some(2));
+        let token_ids: Vec<TokenId> = page.tokens.into_iter().map(|token| token.token_id).collect();
+        assert_eq!(token_ids, vec!["1:3".to_string(), "1:4".to_string()]);
+        assert_eq!(page.next_cursor, Some("1:4".to_string()));
     }
 
     #[test]
-    fn test_nft_transfer_payout() {
+    fn test_nft_token_metadata_inheritance() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1889,7 +4448,7 @@ mod tests {
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
 
-        create_series(&mut contract, &royalty, None, None);
+        create_series(&mut contract, &royalty, None, Some(5));
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1897,68 +4456,52 @@ mod tests {
             .build()
         );
 
-        let token_id = contract.nft_mint("1".to_string(), accounts(2), None);
-
-        testing_env!(context
-            .predecessor_account_id(accounts(2))
-            .attached_deposit(1)
-            .build()
-        );
-
-        let payout = contract.nft_transfer_payout(
-            accounts(3),
-            token_id.clone(),
-            Some(0),
-            Some(U128::from(1 * 10u128.pow(24))),
-            Some(10),
-        );
+        let metadata = TokenMetadata {
+            title: None,
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: Some("{\"level\":3}".to_string()),
+            reference: None,
+            reference_hash: None,
+        };
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), Some(metadata), None, None);
 
-        let mut payout_calc: HashMap<AccountId, U128> = HashMap::new();
-        payout_calc.insert(
-            accounts(1).to_string(),
-            U128::from((1000 * (1 * 10u128.pow(24))) / 10_000),
-        );
-        payout_calc.insert(
-            accounts(2).to_string(),
-            U128::from((9000 * (1 * 10u128.pow(24))) / 10_000),
+        let token_metadata = contract.nft_token(token_id.clone()).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.title.unwrap(), "Tsundere land #1".to_string());
+        assert_eq!(token_metadata.extra.unwrap(), "{\"level\":3}".to_string());
+        assert_eq!(
+            token_metadata.media.unwrap(),
+            "bafybeidzcan4nzcz7sczs4yzyxly4galgygnbjewipj6haco4kffoqpkiy".to_string()
         );
-
-        assert_eq!(payout.unwrap().payout, payout_calc);
-
-        let token = contract.nft_token(token_id).unwrap();
         assert_eq!(
-            token.owner_id,
-            accounts(3).to_string()
-        )
-    }
+            token_metadata.reference.unwrap(),
+            "bafybeicg4ss7qh5odijfn2eogizuxkrdh3zlv4eftcmgnljwu7dm64uwji".to_string()
+        );
+        assert_eq!(token_metadata.copies, Some(5));
 
-    #[test]
-    fn test_create_mint_bundle() {
-        let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .attached_deposit(1)
             .build()
         );
 
-        contract.create_mint_bundle(
-            "test-bundle-test".to_string(),
-            Some(vec!["1".to_string(), "2".to_string()]),
-            None,
-            Some(U128::from(5 * 10u128.pow(24))),
-            None
-        );
-
-        let mint_bundle = contract.get_mint_bundle("test-bundle-test".to_string());
+        contract.nft_set_series_title_format("1".to_string(), TitleFormat::EditionOfCopies);
+        let token_metadata = contract.nft_token(token_id.clone()).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.title.unwrap(), "Tsundere land #1/5".to_string());
 
-        assert_eq!(mint_bundle.token_series_ids, Some(vec!["1".to_string(), "2".to_string()]));
-        assert_eq!(mint_bundle.token_ids, None);
-        assert_eq!(mint_bundle.price.unwrap(), U128::from(5 * 10u128.pow(24)));
-        assert_eq!(mint_bundle.limit_buy, None);
+        contract.nft_set_series_title_format("1".to_string(), TitleFormat::Plain);
+        let token_metadata = contract.nft_token(token_id).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.title.unwrap(), "Tsundere land".to_string());
     }
 
     #[test]
-    fn test_buy_mint_bundle() {
+    fn test_compact_token_data_and_migration() {
         let (mut context, mut contract) = setup_contract();
         testing_env!(context
             .predecessor_account_id(accounts(1))
@@ -1969,124 +4512,140 @@ mod tests {
         let mut royalty: HashMap<AccountId, u32> = HashMap::new();
         royalty.insert(accounts(1).to_string(), 1000);
 
-        create_series(&mut contract, &royalty, None, Some(2));
-        create_series(&mut contract, &royalty, None, Some(2));
-        create_series(&mut contract, &royalty, None, Some(2));
-        create_series(&mut contract, &royalty, None, Some(2));
+        create_series(&mut contract, &royalty, None, None);
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(1_000)
             .build()
         );
 
-        let price = 5 * 10u128.pow(24);
-        let mint_bundle_id = "test-bundle-test".to_string();
-        contract.create_mint_bundle(
-            mint_bundle_id.clone(),
-            Some(vec!["1".to_string(), "2".to_string(), "3".to_string(), "4".to_string()]),
-            None,
-            Some(U128::from(price)),
-            None
-        );
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        let legacy_token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+
+        let token_data = contract.token_data_by_id.get(&token_id).unwrap();
+        assert_eq!(token_data.issued_at, Some(1_000));
+        assert!(token_data.overrides.is_none());
+        let token_metadata = contract.nft_token(token_id).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.issued_at, Some("1000".to_string()));
+        assert_eq!(token_metadata.title.unwrap(), "Tsundere land #1".to_string());
+
+        // token stored the way it was before the upgrade
+        contract.token_data_by_id.remove(&legacy_token_id);
+        let mut legacy_metadata = empty_token_metadata();
+        legacy_metadata.issued_at = Some("500".to_string());
+        contract
+            .tokens
+            .token_metadata_by_id
+            .as_mut()
+            .unwrap()
+            .insert(&legacy_token_id, &legacy_metadata);
 
-        testing_env!(context
-            .predecessor_account_id(accounts(2))
-            .attached_deposit(price + STORAGE_FOR_CREATE_SERIES)
-            .build()
-        );
+        let token_metadata = contract.nft_token(legacy_token_id.clone()).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.issued_at, Some("500".to_string()));
 
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id, accounts(2));
+        assert_eq!(contract.migrate_token_metadata(None, U64::from(10)), None);
+
+        assert!(contract.tokens.token_metadata_by_id.as_ref().unwrap().get(&legacy_token_id).is_none());
+        let token_data = contract.token_data_by_id.get(&legacy_token_id).unwrap();
+        assert_eq!(token_data.issued_at, Some(500));
+        assert!(token_data.overrides.is_none());
+        let token_metadata = contract.nft_token(legacy_token_id).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.issued_at, Some("500".to_string()));
+        assert_eq!(token_metadata.title.unwrap(), "Tsundere land #2".to_string());
     }
 
-    #[test]
-    #[should_panic(expected = "Marble: Mint bundle does not exist or already finished")]
-    fn test_invalid_exhaust_mint_bundle() {
-        let (mut context, mut contract) = setup_contract();
+    fn setup_soulbound_token(transferability: Transferability) -> (VMContextBuilder, Contract, TokenId) {
+        let (mut context, mut contract) = setup_series();
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_set_series_transferability("1".to_string(), transferability);
 
-        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
-        royalty.insert(accounts(1).to_string(), 1000);
+        let token_id = mint_token(&mut context, &mut contract, "1", accounts(2));
 
-        create_series(&mut contract, &royalty, None, Some(2));
+        (context, contract, token_id)
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: token is soulbound")]
+    fn test_invalid_transfer_soulbound() {
+        let (mut context, mut contract, token_id) = setup_soulbound_token(Transferability::Soulbound);
+        assert_eq!(
+            contract.nft_get_series_single("1".to_string()).unwrap().transferability,
+            Transferability::Soulbound
+        );
 
         testing_env!(context
-            .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_transfer(accounts(3), token_id, None, None);
+    }
 
-        let price = 5 * 10u128.pow(24);
-        let mint_bundle_id = "test-bundle-test".to_string();
-        contract.create_mint_bundle(
-            mint_bundle_id.clone(),
-            Some(vec!["1".to_string()]),
-            None,
-            Some(U128::from(price)),
-            None
-        );
+    #[test]
+    #[should_panic(expected = "Marble: token is soulbound")]
+    fn test_invalid_approve_soulbound() {
+        let (mut context, mut contract, token_id) = setup_soulbound_token(Transferability::RevocableSoulbound);
 
         testing_env!(context
             .predecessor_account_id(accounts(2))
-            .attached_deposit(price + STORAGE_FOR_CREATE_SERIES)
+            .attached_deposit(STORAGE_FOR_MINT)
             .build()
         );
-
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id, accounts(2));
+        contract.nft_approve(token_id, accounts(3), None);
     }
 
     #[test]
-    #[should_panic(expected = "Marble: Mint exhausted for account_id charlie")]
-    fn test_invalid_exhaust_limit_buy_mint_bundle() {
-        let (mut context, mut contract) = setup_contract();
+    fn test_revoke_soulbound() {
+        let (mut context, mut contract, token_id) = setup_soulbound_token(Transferability::RevocableSoulbound);
+
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_revoke_soulbound(token_id.clone(), Some("membership ended".to_string()));
 
-        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
-        royalty.insert(accounts(1).to_string(), 1000);
+        assert!(contract.nft_token(token_id).is_none());
+        assert_eq!(contract.nft_get_series_single("1".to_string()).unwrap().burned, U64::from(1));
+        assert_eq!(contract.nft_supply_for_owner(accounts(2)), U128::from(0));
+    }
 
-        create_series(&mut contract, &royalty, None, Some(2));
+    #[test]
+    #[should_panic(expected = "Marble: token is not revocable")]
+    fn test_invalid_revoke_soulbound_not_revocable() {
+        let (mut context, mut contract, token_id) = setup_soulbound_token(Transferability::Soulbound);
 
         testing_env!(context
             .predecessor_account_id(accounts(1))
-            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_revoke_soulbound(token_id, None);
+    }
 
-        let price = 5 * 10u128.pow(24);
-        let mint_bundle_id = "test-bundle-test".to_string();
-        contract.create_mint_bundle(
-            mint_bundle_id.clone(),
-            Some(vec!["1".to_string()]),
-            None,
-            Some(U128::from(price)),
-            Some(1)
-        );
+    #[test]
+    fn test_nft_balance_of_series() {
+        let (mut context, mut contract) = setup_series();
+        let first = mint_token(&mut context, &mut contract, "1", accounts(2));
+        let second = mint_token(&mut context, &mut contract, "1", accounts(2));
+        assert!(contract.nft_holds_at_least(accounts(2), "1".to_string(), U64::from(2)));
 
         testing_env!(context
             .predecessor_account_id(accounts(2))
-            .attached_deposit(price + STORAGE_FOR_CREATE_SERIES)
+            .attached_deposit(1)
             .build()
         );
+        contract.nft_transfer(accounts(3), first, None, None);
+        contract.nft_burn(second);
 
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id.clone(), accounts(2));
-        contract.buy_mint_bundle(mint_bundle_id, accounts(2));
+        assert_eq!(contract.nft_balance_of_series(accounts(2), "1".to_string()), U64::from(0));
+        assert_eq!(contract.nft_balance_of_series(accounts(3), "1".to_string()), U64::from(1));
+        assert!(!contract.nft_holds_at_least(accounts(3), "1".to_string(), U64::from(2)));
     }
 }
\ No newline at end of file
@@ -0,0 +1,368 @@
+use crate::*;
+
+/// Levels of tokens below a top-level token
+const MAX_NESTING_DEPTH: u32 = 3;
+const MAX_CHILDREN_PER_TOKEN: u64 = 20;
+
+#[near_bindgen]
+impl Contract {
+    /// Puts `child_token_id` in the custody of `parent_token_id`: the child is transferred to the
+    /// contract account and follows the parent from then on. The caller must own the child and
+    /// the top-level token above the parent, and pays for the storage.
+    #[payable]
+    pub fn nft_attach(&mut self, parent_token_id: TokenId, child_token_id: TokenId) {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+
+        assert_ne!(parent_token_id, child_token_id, "Marble: token can't be attached to itself");
+        let child_owner_id = self.tokens.owner_by_id.get(&child_token_id).expect("Token not found");
+        assert_eq!(child_owner_id, sender_id, "Token owner only");
+        let root_owner_id = self.nft_root_owner_of(parent_token_id.clone()).expect("Token not found");
+        assert_eq!(root_owner_id, sender_id, "Token owner only");
+
+        // the parent may be nested under the child already
+        let ancestors = self.internal_ancestors(&parent_token_id);
+        assert!(!ancestors.contains(&child_token_id), "Marble: attaching would create a cycle");
+        assert!(
+            ancestors.len() as u32 + 1 + self.subtree_height_by_id.get(&child_token_id).unwrap_or(0) <= MAX_NESTING_DEPTH,
+            "Marble: exceeds max nesting depth of {}",
+            MAX_NESTING_DEPTH
+        );
+
+        let mut children = self.children_by_id.get(&parent_token_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::ChildrenByIdInner {
+                token_hash: env::sha256(parent_token_id.as_bytes()),
+            })
+        });
+        assert!(
+            children.len() < MAX_CHILDREN_PER_TOKEN,
+            "Marble: exceeds {} children per token",
+            MAX_CHILDREN_PER_TOKEN
+        );
+
+        let contract_id = env::current_account_id();
+        let memo = json!({"parent_token_id": parent_token_id}).to_string();
+        self.internal_transfer(&sender_id, &contract_id, &child_token_id, None, Some(memo.clone()));
+
+        children.insert(&child_token_id);
+        self.children_by_id.insert(&parent_token_id, &children);
+        self.parent_by_id.insert(&child_token_id, &parent_token_id);
+        self.internal_update_subtree_height(&parent_token_id);
+
+        env::log(
+            json!({
+                "type": "nft_attach",
+                "params": {
+                    "parent_token_id": parent_token_id,
+                    "child_token_id": child_token_id,
+                    "owner_id": sender_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+        NearEvent::log_nft_transfer(
+            sender_id,
+            contract_id,
+            vec![child_token_id],
+            Some(memo),
+            None,
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+    }
+
+    /// Returns `child_token_id`, with anything attached to it, to the owner of the top-level token
+    #[payable]
+    pub fn nft_detach(&mut self, parent_token_id: TokenId, child_token_id: TokenId) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+
+        assert_eq!(
+            self.parent_by_id.get(&child_token_id),
+            Some(parent_token_id.clone()),
+            "Marble: token is not attached to parent"
+        );
+        let root_owner_id = self.nft_root_owner_of(parent_token_id.clone()).expect("Token not found");
+        assert_eq!(root_owner_id, sender_id, "Token owner only");
+
+        let mut children = self.children_by_id.get(&parent_token_id).unwrap();
+        children.remove(&child_token_id);
+        if children.is_empty() {
+            self.children_by_id.remove(&parent_token_id);
+        } else {
+            self.children_by_id.insert(&parent_token_id, &children);
+        }
+        self.parent_by_id.remove(&child_token_id);
+        self.internal_update_subtree_height(&parent_token_id);
+
+        let contract_id = env::current_account_id();
+        let memo = json!({"parent_token_id": parent_token_id}).to_string();
+        self.internal_release_custody(&root_owner_id, &child_token_id, Some(memo.clone()));
+
+        env::log(
+            json!({
+                "type": "nft_detach",
+                "params": {
+                    "parent_token_id": parent_token_id,
+                    "child_token_id": child_token_id,
+                    "owner_id": root_owner_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+        NearEvent::log_nft_transfer(
+            contract_id,
+            root_owner_id,
+            vec![child_token_id],
+            Some(memo),
+            None,
+        );
+    }
+
+    pub fn nft_parent_of(&self, token_id: TokenId) -> Option<TokenId> {
+        self.parent_by_id.get(&token_id)
+    }
+
+    pub fn nft_children_of(&self, token_id: TokenId, from_index: Option<U128>, limit: Option<u64>) -> Vec<TokenJson> {
+        let children = match self.children_by_id.get(&token_id) {
+            Some(children) => children,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), children.len());
+
+        let child_vector = children.as_vector();
+        (start_index..end_index)
+            .filter_map(|index| child_vector.get(index))
+            .filter_map(|child_token_id| self.nft_token(child_token_id))
+            .collect()
+    }
+
+    /// Owner of the top-level token `token_id` is nested under, or its own owner
+    pub fn nft_root_owner_of(&self, token_id: TokenId) -> Option<AccountId> {
+        let root_token_id = self.internal_ancestors(&token_id).pop().unwrap_or(token_id);
+        self.tokens.owner_by_id.get(&root_token_id)
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_no_children(&self, token_id: &TokenId) {
+        assert!(
+            self.children_by_id.get(token_id).is_none(),
+            "Marble: detach attached tokens first"
+        );
+    }
+
+    pub(crate) fn assert_not_attached(&self, token_id: &TokenId) {
+        assert!(
+            self.parent_by_id.get(token_id).is_none(),
+            "Marble: token is attached to another token"
+        );
+    }
+
+    /// Parent first, top-level token last
+    fn internal_ancestors(&self, token_id: &TokenId) -> Vec<TokenId> {
+        let mut ancestors: Vec<TokenId> = vec![];
+        let mut current = token_id.clone();
+        while let Some(parent_token_id) = self.parent_by_id.get(&current) {
+            assert!(ancestors.len() as u32 <= MAX_NESTING_DEPTH, "Marble: exceeds max nesting depth of {}", MAX_NESTING_DEPTH);
+            ancestors.push(parent_token_id.clone());
+            current = parent_token_id;
+        }
+        ancestors
+    }
+
+    /// Recomputes the stored height of `token_id` from its children after one was attached or
+    /// detached, then of its ancestors until a height stays the same
+    fn internal_update_subtree_height(&mut self, token_id: &TokenId) {
+        let mut current = token_id.clone();
+        loop {
+            let height = match self.children_by_id.get(&current) {
+                Some(children) => children
+                    .iter()
+                    .map(|child_token_id| 1 + self.subtree_height_by_id.get(&child_token_id).unwrap_or(0))
+                    .max()
+                    .unwrap_or(0),
+                None => 0,
+            };
+            if self.subtree_height_by_id.get(&current).unwrap_or(0) == height {
+                return;
+            }
+            if height == 0 {
+                self.subtree_height_by_id.remove(&current);
+            } else {
+                self.subtree_height_by_id.insert(&current, &height);
+            }
+            match self.parent_by_id.get(&current) {
+                Some(parent_token_id) => current = parent_token_id,
+                None => return,
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{mint_token, setup_series, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn setup_tokens(count: usize) -> (VMContextBuilder, Contract, Vec<TokenId>) {
+        let (mut context, mut contract) = setup_series();
+        let token_ids = (0..count)
+            .map(|_| mint_token(&mut context, &mut contract, "1", accounts(2)))
+            .collect();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        (context, contract, token_ids)
+    }
+
+    #[test]
+    fn test_attach_transfer_detach() {
+        let (mut context, mut contract, token_ids) = setup_tokens(2);
+        let (character, item) = (token_ids[0].clone(), token_ids[1].clone());
+
+        contract.nft_attach(character.clone(), item.clone());
+        assert_eq!(contract.nft_parent_of(item.clone()), Some(character.clone()));
+        assert_eq!(contract.nft_token(item.clone()).unwrap().owner_id, accounts(0).to_string());
+        assert_eq!(contract.nft_children_of(character.clone(), None, None).len(), 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer(accounts(3), character.clone(), None, None);
+        assert_eq!(contract.nft_root_owner_of(item.clone()), Some(accounts(3).to_string()));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_detach(character.clone(), item.clone());
+        assert_eq!(contract.nft_token(item.clone()).unwrap().owner_id, accounts(3).to_string());
+        assert!(contract.nft_parent_of(item).is_none());
+        assert!(contract.nft_children_of(character, None, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: exceeds max nesting depth of 3")]
+    fn test_invalid_attach_too_deep() {
+        let (_, mut contract, token_ids) = setup_tokens(5);
+
+        contract.nft_attach(token_ids[0].clone(), token_ids[1].clone());
+        contract.nft_attach(token_ids[1].clone(), token_ids[2].clone());
+        contract.nft_attach(token_ids[2].clone(), token_ids[3].clone());
+        contract.nft_attach(token_ids[3].clone(), token_ids[4].clone());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: attaching would create a cycle")]
+    fn test_invalid_attach_parent_to_own_child() {
+        let (_, mut contract, token_ids) = setup_tokens(2);
+
+        contract.nft_attach(token_ids[0].clone(), token_ids[1].clone());
+        contract.nft_attach(token_ids[1].clone(), token_ids[0].clone());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: detach attached tokens first")]
+    fn test_invalid_burn_parent_with_children() {
+        let (mut context, mut contract, token_ids) = setup_tokens(2);
+
+        contract.nft_attach(token_ids[0].clone(), token_ids[1].clone());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_burn(token_ids[0].clone());
+    }
+
+    #[test]
+    fn test_detach_lowers_subtree_height() {
+        let (mut context, mut contract, token_ids) = setup_tokens(5);
+
+        contract.nft_attach(token_ids[1].clone(), token_ids[2].clone());
+        contract.nft_attach(token_ids[2].clone(), token_ids[3].clone());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_detach(token_ids[2].clone(), token_ids[3].clone());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_attach(token_ids[0].clone(), token_ids[1].clone());
+        contract.nft_attach(token_ids[4].clone(), token_ids[0].clone());
+        assert_eq!(contract.nft_root_owner_of(token_ids[2].clone()), Some(accounts(2).to_string()));
+        assert_eq!(contract.nft_parent_of(token_ids[0].clone()), Some(token_ids[4].clone()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: exceeds max nesting depth of 3")]
+    fn test_invalid_attach_subtree_too_deep() {
+        let (_, mut contract, token_ids) = setup_tokens(5);
+
+        contract.nft_attach(token_ids[1].clone(), token_ids[2].clone());
+        contract.nft_attach(token_ids[2].clone(), token_ids[3].clone());
+        contract.nft_attach(token_ids[0].clone(), token_ids[1].clone());
+        contract.nft_attach(token_ids[4].clone(), token_ids[0].clone());
+    }
+
+    #[test]
+    fn test_detach_expired_token() {
+        let (mut context, mut contract) = setup_series();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .block_timestamp(1_000)
+            .build()
+        );
+        contract.nft_set_series_validity("1".to_string(), Some(SeriesValidity {
+            window: ValidityWindow::Absolute {
+                starts_at: None,
+                expires_at: Some(U64::from(2_000)),
+            },
+            block_transfer_when_expired: true,
+            burnable_when_expired: false,
+        }));
+        let character = mint_token(&mut context, &mut contract, "1", accounts(2));
+        let item = mint_token(&mut context, &mut contract, "1", accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_attach(character.clone(), item.clone());
+
+        // the expired item still leaves the contract's custody
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(2_000)
+            .build()
+        );
+        contract.nft_detach(character, item.clone());
+        assert_eq!(contract.nft_token(item).unwrap().owner_id, accounts(2).to_string());
+    }
+}
@@ -0,0 +1,377 @@
+use crate::*;
+use std::collections::HashSet;
+
+pub type RecipeId = String;
+
+/// Burn `inputs` (series -> quantity) and pay `fee` to mint one token of `output_token_series_id`.
+/// Managed by the output series' creator.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Recipe {
+    inputs: HashMap<TokenSeriesId, u32>,
+    output_token_series_id: TokenSeriesId,
+    fee: Balance,
+    is_active: bool,
+    max_redemptions: Option<u64>,
+    redeemed: u64,
+    limit_per_account: Option<u32>,
+    redeemed_by_account: LookupMap<AccountId, u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecipeJson {
+    recipe_id: RecipeId,
+    creator_id: AccountId,
+    inputs: HashMap<TokenSeriesId, u32>,
+    output_token_series_id: TokenSeriesId,
+    fee: U128,
+    is_active: bool,
+    max_redemptions: Option<U64>,
+    redeemed: U64,
+    limit_per_account: Option<u32>,
+}
+
+const MAX_RECIPE_INPUT_TOKENS: u32 = 10;
+
+#[near_bindgen]
+impl Contract {
+    /// Creator of the output series only. Inputs may come from any series.
+    #[payable]
+    pub fn nft_create_recipe(
+        &mut self,
+        recipe_id: RecipeId,
+        inputs: HashMap<TokenSeriesId, u32>,
+        output_token_series_id: TokenSeriesId,
+        fee: Option<U128>,
+        max_redemptions: Option<U64>,
+        limit_per_account: Option<u32>,
+    ) -> RecipeJson {
+        let initial_storage_usage = env::storage_usage();
+
+        let output_token_series = self.token_series_by_id.get(&output_token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            output_token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(self.recipes.get(&recipe_id).is_none(), "Marble: recipe already exists");
+
+        assert!(!inputs.is_empty(), "Marble: recipe requires inputs");
+        let mut total_input_tokens = 0;
+        for (token_series_id, quantity) in inputs.iter() {
+            assert!(
+                self.token_series_by_id.get(token_series_id).is_some(),
+                "Marble: input token series {} not exist",
+                token_series_id
+            );
+            assert!(*quantity > 0, "Marble: input quantity must be positive");
+            total_input_tokens += quantity;
+        }
+        assert!(
+            total_input_tokens <= MAX_RECIPE_INPUT_TOKENS,
+            "Marble: recipe exceeds {} input tokens",
+            MAX_RECIPE_INPUT_TOKENS
+        );
+
+        let recipe = Recipe {
+            inputs,
+            output_token_series_id,
+            fee: fee.map(|fee| fee.0).unwrap_or(0),
+            is_active: true,
+            max_redemptions: max_redemptions.map(|v| v.0),
+            redeemed: 0,
+            limit_per_account,
+            redeemed_by_account: LookupMap::new(StorageKey::RecipeRedeemedByAccount {
+                recipe_id: recipe_id.clone(),
+            }),
+        };
+        self.recipes.insert(&recipe_id, &recipe);
+
+        let recipe_json = self.internal_recipe_to_json(recipe_id, recipe);
+        env::log(
+            json!({
+                "type": "nft_create_recipe",
+                "params": recipe_json,
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+
+        recipe_json
+    }
+
+    #[payable]
+    pub fn nft_set_recipe_active(&mut self, recipe_id: RecipeId, is_active: bool) {
+        assert_one_yocto();
+
+        let mut recipe = self.recipes.get(&recipe_id).expect("Marble: recipe not exist");
+        let output_token_series = self.token_series_by_id.get(&recipe.output_token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            output_token_series.creator_id,
+            "Marble: Creator only"
+        );
+
+        recipe.is_active = is_active;
+        self.recipes.insert(&recipe_id, &recipe);
+        env::log(
+            json!({
+                "type": "nft_set_recipe_active",
+                "params": {
+                    "recipe_id": recipe_id,
+                    "is_active": is_active,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+    }
+
+    /// Burns `token_ids` of the caller, which must match the recipe inputs exactly, and mints
+    /// the output token to the caller. Attach the fee plus storage for the mint.
+    #[payable]
+    pub fn nft_redeem_recipe(&mut self, recipe_id: RecipeId, token_ids: Vec<TokenId>) -> TokenId {
+        let initial_storage_usage = env::storage_usage();
+        let receiver_id = env::predecessor_account_id();
+
+        let mut recipe = self.recipes.get(&recipe_id).expect("Marble: recipe not exist");
+        assert!(recipe.is_active, "Marble: recipe is not active");
+        assert!(
+            env::attached_deposit() >= recipe.fee,
+            "Marble: attached deposit is less than recipe fee"
+        );
+
+        if let Some(max_redemptions) = recipe.max_redemptions {
+            assert!(recipe.redeemed < max_redemptions, "Marble: recipe exhausted");
+        }
+        recipe.redeemed += 1;
+
+        if let Some(limit_per_account) = recipe.limit_per_account {
+            let redeemed_count = recipe.redeemed_by_account.get(&receiver_id).unwrap_or(0);
+            assert!(
+                redeemed_count < limit_per_account,
+                "Marble: recipe exhausted for account_id {}",
+                receiver_id
+            );
+            recipe.redeemed_by_account.insert(&receiver_id, &(redeemed_count + 1));
+        }
+
+        let unique_token_ids: HashSet<&TokenId> = token_ids.iter().collect();
+        assert_eq!(unique_token_ids.len(), token_ids.len(), "Marble: duplicate token_ids");
+
+        let mut provided: HashMap<TokenSeriesId, u32> = HashMap::new();
+        for token_id in token_ids.iter() {
+            let owner_id = self.tokens.owner_by_id.get(token_id).expect("Token not found");
+            assert_eq!(owner_id, receiver_id, "Token owner only");
+            *provided.entry(get_token_series_id(token_id)).or_insert(0) += 1;
+        }
+        assert_eq!(provided, recipe.inputs, "Marble: token_ids don't match recipe inputs");
+
+        for token_id in token_ids.iter() {
+            self.internal_burn(token_id, &receiver_id);
+        }
+        let token_id = self._nft_mint_series(recipe.output_token_series_id.clone(), receiver_id.clone(), None, None);
+        self.recipes.insert(&recipe_id, &recipe);
+
+        let primary_split = if recipe.fee > 0 {
+            let output_token_series = self.token_series_by_id.get(&recipe.output_token_series_id).unwrap();
+            self.internal_pay_primary_sale(&output_token_series, recipe.fee)
+        } else {
+            HashMap::new()
+        };
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), recipe.fee);
+
+        let memo = json!({"recipe_id": recipe_id}).to_string();
+        NearEvent::log_nft_burn(
+            receiver_id.clone(),
+            token_ids.clone(),
+            Some(memo),
+            None,
+        );
+        NearEvent::log_nft_mint(
+            receiver_id.clone(),
+            vec![token_id.clone()],
+            Some(json!({
+                "recipe_id": recipe_id,
+                "price": recipe.fee.to_string(),
+                "primary_split": primary_split,
+            }).to_string()),
+        );
+        env::log(
+            json!({
+                "type": "nft_redeem_recipe",
+                "params": {
+                    "recipe_id": recipe_id,
+                    "account_id": receiver_id,
+                    "burned_token_ids": token_ids,
+                    "token_id": token_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        token_id
+    }
+
+    pub fn nft_get_recipe(&self, recipe_id: RecipeId) -> Option<RecipeJson> {
+        self.recipes.get(&recipe_id).map(|recipe| self.internal_recipe_to_json(recipe_id, recipe))
+    }
+
+    pub fn nft_get_recipes(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<RecipeJson> {
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), self.recipes.len());
+
+        let keys = self.recipes.keys_as_vector();
+        let values = self.recipes.values_as_vector();
+        (start_index..end_index)
+            .map(|index| self.internal_recipe_to_json(keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect()
+    }
+
+    pub fn nft_recipe_redeemed_by_account(&self, recipe_id: RecipeId, account_id: ValidAccountId) -> u32 {
+        self.recipes
+            .get(&recipe_id)
+            .and_then(|recipe| recipe.redeemed_by_account.get(account_id.as_ref()))
+            .unwrap_or(0)
+    }
+}
+
+impl Contract {
+    /// `creator_id` is the output series' current creator
+    fn internal_recipe_to_json(&self, recipe_id: RecipeId, recipe: Recipe) -> RecipeJson {
+        let output_token_series = self.token_series_by_id.get(&recipe.output_token_series_id).expect("Token series not exist");
+        RecipeJson {
+            recipe_id,
+            creator_id: output_token_series.creator_id,
+            inputs: recipe.inputs,
+            output_token_series_id: recipe.output_token_series_id,
+            fee: U128::from(recipe.fee),
+            is_active: recipe.is_active,
+            max_redemptions: recipe.max_redemptions.map(U64::from),
+            redeemed: U64::from(recipe.redeemed),
+            limit_per_account: recipe.limit_per_account,
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{create_series, mint_token, setup_series, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    /// Series "1" is the input, "2" the output; accounts(2) holds three tokens of "1"
+    fn setup_recipe(fee: Option<U128>) -> (VMContextBuilder, Contract, Vec<TokenId>) {
+        let (mut context, mut contract) = setup_series();
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        let mut inputs: HashMap<TokenSeriesId, u32> = HashMap::new();
+        inputs.insert("1".to_string(), 2);
+        contract.nft_create_recipe("upgrade".to_string(), inputs, "2".to_string(), fee, None, Some(1));
+
+        let token_ids = (0..3)
+            .map(|_| mint_token(&mut context, &mut contract, "1", accounts(2)))
+            .collect();
+
+        (context, contract, token_ids)
+    }
+
+    #[test]
+    fn test_redeem_recipe() {
+        let (mut context, mut contract, token_ids) = setup_recipe(Some(U128::from(1_000_000)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1_000_000 + STORAGE_FOR_MINT)
+            .build()
+        );
+        let token_id = contract.nft_redeem_recipe(
+            "upgrade".to_string(),
+            vec![token_ids[0].clone(), token_ids[1].clone()],
+        );
+
+        assert_eq!(token_id, "2:1".to_string());
+        assert_eq!(contract.nft_token(token_id).unwrap().owner_id, accounts(2).to_string());
+        assert!(contract.nft_token(token_ids[0].clone()).is_none());
+        assert!(contract.nft_token(token_ids[1].clone()).is_none());
+        assert_eq!(contract.nft_get_series_single("1".to_string()).unwrap().burned, U64::from(2));
+
+        let recipe = contract.nft_get_recipe("upgrade".to_string()).unwrap();
+        assert_eq!(recipe.redeemed, U64::from(1));
+        assert_eq!(contract.nft_recipe_redeemed_by_account("upgrade".to_string(), accounts(2)), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: token_ids don't match recipe inputs")]
+    fn test_invalid_redeem_recipe_missing_input() {
+        let (mut context, mut contract, token_ids) = setup_recipe(None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_redeem_recipe("upgrade".to_string(), vec![token_ids[0].clone()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token owner only")]
+    fn test_invalid_redeem_recipe_not_owner() {
+        let (mut context, mut contract, token_ids) = setup_recipe(None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_redeem_recipe("upgrade".to_string(), vec![token_ids[0].clone(), token_ids[1].clone()]);
+    }
+
+    #[test]
+    fn test_recipe_follows_series_creator() {
+        let (mut context, mut contract, _) = setup_recipe(None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_force_series_creator("2".to_string(), accounts(3), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_set_recipe_active("upgrade".to_string(), false);
+
+        let recipe = contract.nft_get_recipe("upgrade".to_string()).unwrap();
+        assert_eq!(recipe.creator_id, accounts(3).to_string());
+        assert!(!recipe.is_active);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Creator only")]
+    fn test_invalid_set_recipe_active_previous_creator() {
+        let (mut context, mut contract, _) = setup_recipe(None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_force_series_creator("2".to_string(), accounts(3), None);
+        contract.nft_set_recipe_active("upgrade".to_string(), false);
+    }
+}
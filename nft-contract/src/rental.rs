@@ -0,0 +1,235 @@
+use crate::*;
+
+/// Account allowed to use a token until `expires` (nanoseconds), without owning it
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TokenUser {
+    user_id: AccountId,
+    expires: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenUserJson {
+    user_id: AccountId,
+    expires: U64,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sets or, with `user_id` None, clears the user of `token_id`. Callable by the owner, an
+    /// operator or an account with an unexpired approval. The caller pays for the storage.
+    #[payable]
+    pub fn nft_set_user(&mut self, token_id: TokenId, user_id: Option<ValidAccountId>, expires: U64) {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+
+        self.assert_owner_or_approved(&token_id);
+        let predecessor_account_id = env::predecessor_account_id();
+
+        self.internal_remove_token_user(&token_id);
+        if let Some(user_id) = &user_id {
+            assert!(expires.0 > env::block_timestamp(), "Marble: expires must be in the future");
+            let user_id: AccountId = user_id.to_string();
+            self.token_user_by_id.insert(&token_id, &TokenUser {
+                user_id: user_id.clone(),
+                expires: expires.0,
+            });
+            let mut token_ids = self.tokens_per_user.get(&user_id).unwrap_or_else(|| {
+                UnorderedSet::new(StorageKey::TokensPerUserInner {
+                    account_hash: env::sha256(user_id.as_bytes()),
+                })
+            });
+            token_ids.insert(&token_id);
+            self.tokens_per_user.insert(&user_id, &token_ids);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_set_user",
+                "params": {
+                    "token_id": token_id,
+                    "user_id": user_id,
+                    "expires": user_id.as_ref().map(|_| expires),
+                    "authorized_id": predecessor_account_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+    }
+
+    /// None once the rental has expired
+    pub fn nft_user_of(&self, token_id: TokenId) -> Option<TokenUserJson> {
+        self.token_user_by_id
+            .get(&token_id)
+            .filter(|token_user| token_user.expires > env::block_timestamp())
+            .map(|token_user| TokenUserJson {
+                user_id: token_user.user_id,
+                expires: U64::from(token_user.expires),
+            })
+    }
+
+    /// Tokens `account_id` currently has user rights on. Expired rentals are skipped, so a page can
+    /// hold fewer than `limit` tokens.
+    pub fn nft_tokens_for_user(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenJson> {
+        let token_ids = match self.tokens_per_user.get(account_id.as_ref()) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), token_ids.len());
+
+        let token_vector = token_ids.as_vector();
+        (start_index..end_index)
+            .filter_map(|index| token_vector.get(index))
+            .filter(|token_id| self.nft_user_of(token_id.clone()).is_some())
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Called on transfer and burn. Logs `nft_set_user` with a null user when one was set.
+    pub(crate) fn internal_clear_token_user(&mut self, token_id: &TokenId) {
+        if self.internal_remove_token_user(token_id).is_none() {
+            return;
+        }
+        env::log(
+            json!({
+                "type": "nft_set_user",
+                "params": {
+                    "token_id": token_id,
+                    "user_id": Option::<AccountId>::None,
+                    "expires": Option::<U64>::None,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+    }
+
+    fn internal_remove_token_user(&mut self, token_id: &TokenId) -> Option<TokenUser> {
+        let token_user = self.token_user_by_id.remove(token_id)?;
+        if let Some(mut token_ids) = self.tokens_per_user.get(&token_user.user_id) {
+            token_ids.remove(token_id);
+            if token_ids.is_empty() {
+                self.tokens_per_user.remove(&token_user.user_id);
+            } else {
+                self.tokens_per_user.insert(&token_user.user_id, &token_ids);
+            }
+        }
+        Some(token_user)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{setup_token, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_set_user_and_clear_on_transfer() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(1_000)
+            .build()
+        );
+        contract.nft_set_user(token_id.clone(), Some(accounts(3)), U64::from(2_000));
+
+        let token_user = contract.nft_user_of(token_id.clone()).unwrap();
+        assert_eq!(token_user.user_id, accounts(3).to_string());
+        assert_eq!(token_user.expires, U64::from(2_000));
+        assert_eq!(contract.nft_tokens_for_user(accounts(3), None, None).len(), 1);
+
+        testing_env!(context.block_timestamp(2_000).build());
+        assert!(contract.nft_user_of(token_id.clone()).is_none());
+        assert!(contract.nft_tokens_for_user(accounts(3), None, None).is_empty());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(2_500)
+            .build()
+        );
+        contract.nft_set_user(token_id.clone(), Some(accounts(3)), U64::from(5_000));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer(accounts(4), token_id.clone(), None, None);
+
+        assert!(contract.nft_user_of(token_id).is_none());
+        assert!(contract.tokens_per_user.get(&accounts(3).to_string()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Owner or approved account only")]
+    fn test_invalid_set_user_not_owner() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_set_user(token_id, Some(accounts(3)), U64::from(2_000));
+    }
+
+    #[test]
+    fn test_set_user_by_operator() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_set_approval_for_all(accounts(3), true, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_set_user(token_id.clone(), Some(accounts(4)), U64::from(2_000));
+        assert_eq!(contract.nft_user_of(token_id).unwrap().user_id, accounts(4).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Owner or approved account only")]
+    fn test_invalid_set_user_expired_approval() {
+        let (mut context, mut contract, token_id) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(10)
+            .build()
+        );
+        contract.nft_approve_many(token_id.clone(), vec![accounts(3)], None, Some(U64::from(100)), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(100)
+            .build()
+        );
+        contract.nft_set_user(token_id, Some(accounts(3)), U64::from(2_000));
+    }
+}
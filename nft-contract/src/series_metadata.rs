@@ -0,0 +1,289 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+
+/// Older versions are dropped first
+const MAX_SERIES_METADATA_HISTORY: usize = 10;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SeriesMetadataVersion {
+    updated_at: u64,
+    fields_changed: Vec<String>,
+    old_reference: Option<String>,
+    old_reference_hash: Option<Base64VecU8>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesMetadataVersionJson {
+    updated_at: U64,
+    fields_changed: Vec<String>,
+    old_reference: Option<String>,
+    old_reference_hash: Option<Base64VecU8>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Replaces the series metadata. `title` is required and `copies` can only go down to the
+    /// minted count. The caller pays for the history entry.
+    #[payable]
+    pub fn nft_update_series_metadata(&mut self, token_series_id: TokenSeriesId, metadata: TokenMetadata) -> Vec<String> {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(!token_series.metadata_frozen, "Marble: series metadata is frozen");
+        assert!(
+            metadata.title.as_ref().map(|title| !title.is_empty()).unwrap_or(false),
+            "Marble: title is required"
+        );
+
+        let minted_copies = token_series.tokens.len();
+        if let Some(copies) = metadata.copies {
+            assert!(copies >= minted_copies, "Marble: copies less than minted : {}", minted_copies);
+            assert!(
+                copies <= token_series.metadata.copies.unwrap_or(u64::MAX),
+                "Marble: copies can't be increased"
+            );
+            if copies == minted_copies {
+                token_series.is_mintable = false;
+            }
+        } else {
+            assert!(token_series.metadata.copies.is_none(), "Marble: copies can't be removed");
+        }
+
+        let fields_changed = changed_metadata_fields(&token_series.metadata, &metadata);
+        assert!(!fields_changed.is_empty(), "Marble: metadata unchanged");
+
+        self.internal_push_series_metadata_history(&token_series_id, &token_series.metadata, fields_changed.clone());
+
+        token_series.metadata = metadata;
+        self.token_series_by_id.insert(&token_series_id, &token_series);
+
+        env::log(
+            json!({
+                "type": "nft_update_series_metadata",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "fields_changed": fields_changed,
+                    "token_metadata": token_series.metadata,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+
+        fields_changed
+    }
+
+    /// Makes the series metadata permanent, there is no way back
+    #[payable]
+    pub fn nft_freeze_series_metadata(&mut self, token_series_id: TokenSeriesId) {
+        assert_one_yocto();
+
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(!token_series.metadata_frozen, "Marble: series metadata is frozen");
+
+        token_series.metadata_frozen = true;
+        self.token_series_by_id.insert(&token_series_id, &token_series);
+        env::log(
+            json!({
+                "type": "nft_freeze_series_metadata",
+                "params": {
+                    "token_series_id": token_series_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+    }
+
+    /// Oldest first
+    pub fn nft_get_series_metadata_history(&self, token_series_id: TokenSeriesId) -> Vec<SeriesMetadataVersionJson> {
+        self.series_metadata_history
+            .get(&token_series_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|version| SeriesMetadataVersionJson {
+                updated_at: U64::from(version.updated_at),
+                fields_changed: version.fields_changed,
+                old_reference: version.old_reference,
+                old_reference_hash: version.old_reference_hash,
+            })
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_push_series_metadata_history(
+        &mut self,
+        token_series_id: &TokenSeriesId,
+        old_metadata: &TokenMetadata,
+        fields_changed: Vec<String>,
+    ) {
+        let mut history = self.series_metadata_history.get(token_series_id).unwrap_or_default();
+        if history.len() >= MAX_SERIES_METADATA_HISTORY {
+            history.remove(0);
+        }
+        history.push(SeriesMetadataVersion {
+            updated_at: env::block_timestamp(),
+            fields_changed,
+            old_reference: old_metadata.reference.clone(),
+            old_reference_hash: old_metadata.reference_hash.clone(),
+        });
+        self.series_metadata_history.insert(token_series_id, &history);
+    }
+}
+
+fn changed_metadata_fields(old: &TokenMetadata, new: &TokenMetadata) -> Vec<String> {
+    let changes = [
+        ("title", old.title != new.title),
+        ("description", old.description != new.description),
+        ("media", old.media != new.media),
+        ("media_hash", old.media_hash != new.media_hash),
+        ("copies", old.copies != new.copies),
+        ("issued_at", old.issued_at != new.issued_at),
+        ("expires_at", old.expires_at != new.expires_at),
+        ("starts_at", old.starts_at != new.starts_at),
+        ("updated_at", old.updated_at != new.updated_at),
+        ("extra", old.extra != new.extra),
+        ("reference", old.reference != new.reference),
+        ("reference_hash", old.reference_hash != new.reference_hash),
+    ];
+    changes
+        .iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(field, _)| field.to_string())
+        .collect()
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{create_series, setup_contract, setup_token, STORAGE_FOR_CREATE_SERIES, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn series_metadata(contract: &Contract) -> TokenMetadata {
+        contract.nft_get_series_single("1".to_string()).unwrap().metadata
+    }
+
+    /// Series "1" by accounts(1) limited to 5 copies
+    fn setup_capped_series() -> (VMContextBuilder, Contract) {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        create_series(&mut contract, &HashMap::new(), None, Some(5));
+
+        (context, contract)
+    }
+
+    #[test]
+    fn test_update_series_metadata_history() {
+        let (mut context, mut contract, _) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(42)
+            .build()
+        );
+        let old_reference = series_metadata(&contract).reference;
+        let mut metadata = series_metadata(&contract);
+        metadata.title = Some("Tsundere land, revised".to_string());
+        metadata.reference = Some("bafybeihnoou2av5w2bzmwkl6hi25scyzz6sjwdfqp4cwq2ikf6dfmev3ta".to_string());
+        metadata.copies = Some(1);
+
+        let fields_changed = contract.nft_update_series_metadata("1".to_string(), metadata);
+        assert_eq!(fields_changed, vec!["title", "copies", "reference"]);
+
+        let series = contract.nft_get_series_single("1".to_string()).unwrap();
+        assert_eq!(series.metadata.title, Some("Tsundere land, revised".to_string()));
+        assert!(!series.is_mintable);
+
+        let history = contract.nft_get_series_metadata_history("1".to_string());
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].updated_at, U64::from(42));
+        assert_eq!(history[0].old_reference, old_reference);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: copies less than minted : 1")]
+    fn test_invalid_update_series_copies_below_minted() {
+        let (mut context, mut contract, _) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        let mut metadata = series_metadata(&contract);
+        metadata.copies = Some(0);
+        contract.nft_update_series_metadata("1".to_string(), metadata);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: series metadata is frozen")]
+    fn test_invalid_update_frozen_series_metadata() {
+        let (mut context, mut contract, _) = setup_token();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_freeze_series_metadata("1".to_string());
+        assert!(contract.nft_get_series_single("1".to_string()).unwrap().metadata_frozen);
+
+        let mut metadata = series_metadata(&contract);
+        metadata.description = Some("typo fixed".to_string());
+        contract.nft_update_series_metadata("1".to_string(), metadata);
+    }
+
+    #[test]
+    fn test_decrease_series_copies_history() {
+        let (mut context, mut contract) = setup_capped_series();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        let old_reference = series_metadata(&contract).reference;
+        contract.nft_decrease_series_copies("1".to_string(), U64::from(1));
+
+        let history = contract.nft_get_series_metadata_history("1".to_string());
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].fields_changed, vec!["copies"]);
+        assert_eq!(history[0].old_reference, old_reference);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: series metadata is frozen")]
+    fn test_invalid_decrease_frozen_series_copies() {
+        let (mut context, mut contract) = setup_capped_series();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_freeze_series_metadata("1".to_string());
+        contract.nft_decrease_series_copies("1".to_string(), U64::from(1));
+    }
+}
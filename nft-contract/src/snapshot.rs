@@ -0,0 +1,460 @@
+use crate::*;
+use near_sdk::collections::LookupSet;
+
+pub type SnapshotId = String;
+
+const DEFAULT_SNAPSHOT_CHUNK: u64 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct HolderSnapshot {
+    token_series_id: Option<TokenSeriesId>,
+    created_by: AccountId,
+    created_at: u64,
+    started_at_block: u64,
+    completed_at_block: Option<u64>,
+    /// Last edition walked for series snapshots, or the last token id walked for contract-wide snapshots
+    cursor: Option<String>,
+    /// Editions minted when a series snapshot started, later ones aren't counted
+    last_edition: Option<u64>,
+    is_complete: bool,
+    holders: UnorderedMap<AccountId, u64>,
+    /// Tokens ahead of the walk that changed owner after the snapshot started. They were counted
+    /// for their owner at the start right then, or left out when minted after it.
+    settled: LookupSet<TokenId>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HolderSnapshotJson {
+    snapshot_id: SnapshotId,
+    token_series_id: Option<TokenSeriesId>,
+    created_by: AccountId,
+    created_at: U64,
+    started_at_block: U64,
+    completed_at_block: Option<U64>,
+    is_complete: bool,
+    holders_count: U64,
+}
+
+impl HolderSnapshot {
+    /// Whether the walk still has to reach `token_id`
+    fn is_ahead_of_walk(&self, token_id: &TokenId) -> bool {
+        match self.last_edition {
+            Some(last_edition) => {
+                let walked: u64 = self.cursor.as_ref().map(|edition| edition.parse().unwrap()).unwrap_or(0);
+                token_id
+                    .split(TOKEN_DELIMETER)
+                    .nth(1)
+                    .and_then(|edition| edition.parse::<u64>().ok())
+                    .map(|edition| edition > walked && edition <= last_edition)
+                    .unwrap_or(false)
+            }
+            None => self.cursor.as_ref().map(|cursor| token_id > cursor).unwrap_or(true),
+        }
+    }
+
+    fn add_holder(&mut self, owner_id: &AccountId) {
+        let count = self.holders.get(owner_id).unwrap_or(0);
+        self.holders.insert(owner_id, &(count + 1));
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Records who holds `token_series_id` (or any token when None) as of the first call,
+    /// walking `limit` tokens per call in id order: call again with the same `snapshot_id`
+    /// until it returns true. Tokens that change owner before the walk reaches them are counted
+    /// for their owner at the start, tokens minted after it are left out. Snapshot ids are per
+    /// caller, and a series (or the whole contract) has one snapshot in progress at a time.
+    #[payable]
+    pub fn nft_holders_snapshot(
+        &mut self,
+        snapshot_id: SnapshotId,
+        token_series_id: Option<TokenSeriesId>,
+        limit: Option<u64>,
+    ) -> bool {
+        let initial_storage_usage = env::storage_usage();
+        self.assert_can_snapshot(&token_series_id);
+        let limit = limit.unwrap_or(DEFAULT_SNAPSHOT_CHUNK);
+        assert!(limit > 0, "Marble: limit must be greater than 0");
+
+        let snapshot_key = (env::predecessor_account_id(), snapshot_id.clone());
+        let mut snapshot = match self.holder_snapshots.get(&snapshot_key) {
+            Some(snapshot) => {
+                assert_eq!(snapshot.token_series_id, token_series_id, "Marble: snapshot series mismatch");
+                assert!(!snapshot.is_complete, "Marble: snapshot already complete");
+                snapshot
+            }
+            None => self.internal_open_snapshot(&snapshot_key, &token_series_id),
+        };
+
+        let owner_ids: Vec<AccountId> = if let Some(token_series_id) = &token_series_id {
+            // editions never move or get reused, unlike positions in the owner index
+            let last_edition = snapshot.last_edition.unwrap();
+            let start_edition: u64 = snapshot.cursor.as_ref().map(|edition| edition.parse().unwrap()).unwrap_or(0);
+            let end_edition = std::cmp::min(start_edition.saturating_add(limit), last_edition);
+            snapshot.cursor = Some(end_edition.to_string());
+            snapshot.is_complete = end_edition == last_edition;
+            (start_edition + 1..=end_edition)
+                .map(|edition| format!("{}{}{}", token_series_id, TOKEN_DELIMETER, edition))
+                .filter(|token_id| !snapshot.settled.contains(token_id))
+                .filter_map(|token_id| self.tokens.owner_by_id.get(&token_id))
+                .collect()
+        } else {
+            let tokens: Vec<(TokenId, AccountId)> = match snapshot.cursor.clone() {
+                Some(cursor) => self.tokens.owner_by_id.range((Bound::Excluded(cursor), Bound::Unbounded)).take(limit as usize).collect(),
+                None => self.tokens.owner_by_id.iter().take(limit as usize).collect(),
+            };
+            if let Some((token_id, _)) = tokens.last() {
+                snapshot.cursor = Some(token_id.clone());
+            }
+            snapshot.is_complete = (tokens.len() as u64) < limit;
+            tokens
+                .into_iter()
+                .filter(|(token_id, _)| !snapshot.settled.contains(token_id))
+                .map(|(_, owner_id)| owner_id)
+                .collect()
+        };
+
+        for owner_id in owner_ids.iter() {
+            snapshot.add_holder(owner_id);
+        }
+        if snapshot.is_complete {
+            snapshot.completed_at_block = Some(env::block_index());
+            self.internal_close_snapshot(&token_series_id);
+        }
+
+        self.holder_snapshots.insert(&snapshot_key, &snapshot);
+
+        if snapshot.is_complete {
+            env::log(
+                json!({
+                    "type": "nft_holders_snapshot",
+                    "params": {
+                        "snapshot_id": snapshot_id,
+                        "created_by": snapshot.created_by,
+                        "token_series_id": token_series_id,
+                        "created_at": U64::from(snapshot.created_at),
+                        "started_at_block": U64::from(snapshot.started_at_block),
+                        "completed_at_block": snapshot.completed_at_block.map(U64::from),
+                        "holders_count": U64::from(snapshot.holders.len()),
+                    }
+                })
+                    .to_string()
+                    .as_bytes(),
+            );
+        }
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+
+        snapshot.is_complete
+    }
+
+    pub fn nft_get_holders_snapshot(&self, created_by: ValidAccountId, snapshot_id: SnapshotId) -> Option<HolderSnapshotJson> {
+        self.holder_snapshots.get(&(created_by.into(), snapshot_id.clone())).map(|snapshot| HolderSnapshotJson {
+            snapshot_id,
+            token_series_id: snapshot.token_series_id,
+            created_by: snapshot.created_by,
+            created_at: U64::from(snapshot.created_at),
+            started_at_block: U64::from(snapshot.started_at_block),
+            completed_at_block: snapshot.completed_at_block.map(U64::from),
+            is_complete: snapshot.is_complete,
+            holders_count: U64::from(snapshot.holders.len()),
+        })
+    }
+
+    pub fn nft_get_holders_snapshot_holders(
+        &self,
+        created_by: ValidAccountId,
+        snapshot_id: SnapshotId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<OwnerCountJson> {
+        let snapshot = match self.holder_snapshots.get(&(created_by.into(), snapshot_id)) {
+            Some(snapshot) => snapshot,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), snapshot.holders.len());
+
+        let account_ids = snapshot.holders.keys_as_vector();
+        let counts = snapshot.holders.values_as_vector();
+        (start_index..end_index)
+            .map(|index| OwnerCountJson {
+                account_id: account_ids.get(index).unwrap(),
+                count: U64::from(counts.get(index).unwrap()),
+            })
+            .collect()
+    }
+
+    pub fn nft_holders_snapshot_balance(
+        &self,
+        created_by: ValidAccountId,
+        snapshot_id: SnapshotId,
+        account_id: ValidAccountId,
+    ) -> U64 {
+        self.holder_snapshots
+            .get(&(created_by.into(), snapshot_id))
+            .and_then(|snapshot| snapshot.holders.get(account_id.as_ref()))
+            .unwrap_or(0)
+            .into()
+    }
+
+    /// Contract owner, or the series creator for series snapshots
+    fn assert_can_snapshot(&self, token_series_id: &Option<TokenSeriesId>) {
+        let predecessor_account_id = env::predecessor_account_id();
+        if predecessor_account_id == self.tokens.owner_id {
+            return;
+        }
+        let token_series_id = token_series_id.as_ref().expect("Marble: Only owner");
+        let token_series = self.token_series_by_id.get(token_series_id).expect("Token series not exist");
+        assert_eq!(
+            predecessor_account_id,
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+    }
+}
+
+impl Contract {
+    fn internal_open_snapshot(
+        &mut self,
+        snapshot_key: &(AccountId, SnapshotId),
+        token_series_id: &Option<TokenSeriesId>,
+    ) -> HolderSnapshot {
+        let last_edition = match token_series_id {
+            Some(token_series_id) => {
+                assert!(
+                    self.open_snapshot_by_series.get(token_series_id).is_none(),
+                    "Marble: a snapshot of this series is in progress"
+                );
+                self.open_snapshot_by_series.insert(token_series_id, snapshot_key);
+                Some(self.token_series_by_id.get(token_series_id).expect("Token series not exist").tokens.len())
+            }
+            None => {
+                assert!(self.open_contract_snapshot.is_none(), "Marble: a snapshot of every token is in progress");
+                self.open_contract_snapshot = Some(snapshot_key.clone());
+                None
+            }
+        };
+        let snapshot_hash = env::sha256(format!("{}:{}", snapshot_key.0, snapshot_key.1).as_bytes());
+
+        HolderSnapshot {
+            token_series_id: token_series_id.clone(),
+            created_by: snapshot_key.0.clone(),
+            created_at: env::block_timestamp(),
+            started_at_block: env::block_index(),
+            completed_at_block: None,
+            cursor: None,
+            last_edition,
+            is_complete: false,
+            holders: UnorderedMap::new(StorageKey::HolderSnapshotInner {
+                snapshot_hash: snapshot_hash.clone(),
+            }),
+            settled: LookupSet::new(StorageKey::HolderSnapshotSettled { snapshot_hash }),
+        }
+    }
+
+    fn internal_close_snapshot(&mut self, token_series_id: &Option<TokenSeriesId>) {
+        match token_series_id {
+            Some(token_series_id) => {
+                self.open_snapshot_by_series.remove(token_series_id);
+            }
+            None => self.open_contract_snapshot = None,
+        }
+    }
+
+    /// Called before `token_id` changes owner: snapshots in progress that haven't walked it yet
+    /// count it for its current owner, the one it had when they started. `from` is None on mint.
+    pub(crate) fn internal_settle_snapshots(&mut self, token_id: &TokenId, from: Option<&AccountId>) {
+        let snapshot_keys: Vec<(AccountId, SnapshotId)> = self
+            .open_snapshot_by_series
+            .get(&get_token_series_id(token_id))
+            .into_iter()
+            .chain(self.open_contract_snapshot.clone())
+            .collect();
+
+        for snapshot_key in snapshot_keys {
+            let mut snapshot = self.holder_snapshots.get(&snapshot_key).unwrap();
+            if !snapshot.is_ahead_of_walk(token_id) || snapshot.settled.contains(token_id) {
+                continue;
+            }
+            if let Some(from) = from {
+                snapshot.add_holder(from);
+            }
+            snapshot.settled.insert(token_id);
+            self.holder_snapshots.insert(&snapshot_key, &snapshot);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{create_series, mint_token, setup_contract, setup_series, STORAGE_FOR_CREATE_SERIES, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_owners_for_series_and_snapshot() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        let mut royalty: HashMap<AccountId, u32> = HashMap::new();
+        royalty.insert(accounts(1).to_string(), 1000);
+
+        create_series(&mut contract, &royalty, None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        contract.nft_mint("1".to_string(), accounts(3), None, None, None);
+
+        assert_eq!(contract.nft_owners_count_for_series("1".to_string()), U64::from(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        assert!(!contract.nft_holders_snapshot("airdrop-1".to_string(), Some("1".to_string()), Some(2)));
+        assert!(contract.nft_holders_snapshot("airdrop-1".to_string(), Some("1".to_string()), Some(2)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer(accounts(3), token_id, None, None);
+
+        let owners = contract.nft_owners_for_series("1".to_string(), None, None);
+        let mut owner_counts: HashMap<AccountId, u64> = HashMap::new();
+        for owner in owners {
+            owner_counts.insert(owner.account_id, owner.count.0);
+        }
+        assert_eq!(owner_counts.get(&accounts(2).to_string()), Some(&1));
+        assert_eq!(owner_counts.get(&accounts(3).to_string()), Some(&2));
+
+        // snapshot is unaffected by the transfer
+        assert_eq!(contract.nft_holders_snapshot_balance(accounts(1), "airdrop-1".to_string(), accounts(2)), U64::from(2));
+        assert_eq!(contract.nft_holders_snapshot_balance(accounts(1), "airdrop-1".to_string(), accounts(3)), U64::from(1));
+        let snapshot = contract.nft_get_holders_snapshot(accounts(1), "airdrop-1".to_string()).unwrap();
+        assert!(snapshot.is_complete);
+        assert_eq!(snapshot.holders_count, U64::from(2));
+    }
+
+    #[test]
+    fn test_snapshot_is_point_in_time() {
+        let (mut context, mut contract) = setup_series();
+        mint_token(&mut context, &mut contract, "1", accounts(2));
+        let token_id = mint_token(&mut context, &mut contract, "1", accounts(3));
+        mint_token(&mut context, &mut contract, "1", accounts(4));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .block_index(10)
+            .build()
+        );
+        assert!(!contract.nft_holders_snapshot("airdrop-1".to_string(), Some("1".to_string()), Some(1)));
+
+        // edition 2 changes owner and edition 4 is minted before the walk gets there
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .block_index(15)
+            .build()
+        );
+        contract.nft_transfer(accounts(2), token_id, None, None);
+        mint_token(&mut context, &mut contract, "1", accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .block_index(20)
+            .build()
+        );
+        assert!(!contract.nft_holders_snapshot("airdrop-1".to_string(), Some("1".to_string()), Some(1)));
+        assert!(contract.nft_holders_snapshot("airdrop-1".to_string(), Some("1".to_string()), Some(1)));
+
+        // holders as of block 10
+        assert_eq!(contract.nft_holders_snapshot_balance(accounts(1), "airdrop-1".to_string(), accounts(2)), U64::from(1));
+        assert_eq!(contract.nft_holders_snapshot_balance(accounts(1), "airdrop-1".to_string(), accounts(3)), U64::from(1));
+        assert_eq!(contract.nft_holders_snapshot_balance(accounts(1), "airdrop-1".to_string(), accounts(4)), U64::from(1));
+        let snapshot = contract.nft_get_holders_snapshot(accounts(1), "airdrop-1".to_string()).unwrap();
+        assert_eq!(snapshot.started_at_block, U64::from(10));
+        assert_eq!(snapshot.completed_at_block, Some(U64::from(20)));
+    }
+
+    #[test]
+    fn test_contract_snapshot_is_point_in_time() {
+        let (mut context, mut contract) = setup_series();
+        mint_token(&mut context, &mut contract, "1", accounts(2));
+        let token_id = mint_token(&mut context, &mut contract, "1", accounts(3));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        assert!(!contract.nft_holders_snapshot("all".to_string(), None, Some(1)));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_burn(token_id);
+        mint_token(&mut context, &mut contract, "1", accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        assert!(contract.nft_holders_snapshot("all".to_string(), None, Some(10)));
+
+        assert_eq!(contract.nft_holders_snapshot_balance(accounts(1), "all".to_string(), accounts(2)), U64::from(1));
+        assert_eq!(contract.nft_holders_snapshot_balance(accounts(1), "all".to_string(), accounts(3)), U64::from(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: a snapshot of this series is in progress")]
+    fn test_invalid_second_series_snapshot_in_progress() {
+        let (mut context, mut contract) = setup_series();
+        mint_token(&mut context, &mut contract, "1", accounts(2));
+        mint_token(&mut context, &mut contract, "1", accounts(3));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+        assert!(!contract.nft_holders_snapshot("airdrop-1".to_string(), Some("1".to_string()), Some(1)));
+        contract.nft_holders_snapshot("airdrop-2".to_string(), Some("1".to_string()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: Only owner")]
+    fn test_invalid_contract_snapshot_non_owner() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_CREATE_SERIES)
+            .build()
+        );
+
+        contract.nft_holders_snapshot("all".to_string(), None, None);
+    }
+}
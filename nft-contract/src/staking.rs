@@ -0,0 +1,406 @@
+use crate::*;
+
+const MAX_STAKED_PER_ACCOUNT: u64 = 100;
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Points accrue from `since` at the series rate captured when the token was staked
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Stake {
+    owner_id: AccountId,
+    since: u64,
+    points_per_second: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeJson {
+    token_id: TokenId,
+    owner_id: AccountId,
+    since: U64,
+    points_per_second: U128,
+    pending_points: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Applies to tokens staked afterwards. 0 stops new stakes.
+    #[payable]
+    pub fn nft_set_series_staking_rate(&mut self, token_series_id: TokenSeriesId, points_per_second: U128) {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+
+        let token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+
+        if points_per_second.0 == 0 {
+            self.staking_rate_by_series.remove(&token_series_id);
+        } else {
+            self.staking_rate_by_series.insert(&token_series_id, &points_per_second.0);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_set_series_staking_rate",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "points_per_second": points_per_second,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+    }
+
+    /// Locks `token_ids` of the caller: they can't be transferred, approved or burned until unstaked
+    #[payable]
+    pub fn nft_stake(&mut self, token_ids: Vec<TokenId>) {
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+
+        let mut staked_token_ids = self.staked_tokens_per_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::StakedTokensPerOwnerInner {
+                account_hash: env::sha256(owner_id.as_bytes()),
+            })
+        });
+        assert!(
+            staked_token_ids.len() + token_ids.len() as u64 <= MAX_STAKED_PER_ACCOUNT,
+            "Marble: exceeds {} staked tokens per account",
+            MAX_STAKED_PER_ACCOUNT
+        );
+
+        for token_id in token_ids.iter() {
+            let token_owner_id = self.tokens.owner_by_id.get(token_id).expect("Token not found");
+            assert_eq!(token_owner_id, owner_id, "Token owner only");
+            assert!(self.stakes_by_id.get(token_id).is_none(), "Marble: token is staked");
+            // the creator may burn these, which a stake would block
+            let token_series = self.token_series_by_id.get(&get_token_series_id(token_id)).expect("Token series not exist");
+            assert_ne!(
+                token_series.transferability,
+                Transferability::RevocableSoulbound,
+                "Marble: revocable tokens can't be staked"
+            );
+            assert!(
+                !token_series.validity.map(|validity| validity.burnable_when_expired).unwrap_or(false),
+                "Marble: tokens burnable when expired can't be staked"
+            );
+            let points_per_second = self
+                .staking_rate_by_series
+                .get(&get_token_series_id(token_id))
+                .expect("Marble: staking is not enabled for series");
+
+            self.stakes_by_id.insert(token_id, &Stake {
+                owner_id: owner_id.clone(),
+                since: env::block_timestamp(),
+                points_per_second,
+            });
+            staked_token_ids.insert(token_id);
+        }
+        self.staked_tokens_per_owner.insert(&owner_id, &staked_token_ids);
+
+        env::log(
+            json!({
+                "type": "nft_stake",
+                "params": {
+                    "owner_id": owner_id,
+                    "token_ids": token_ids,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+    }
+
+    /// Credits the points accrued so far and unlocks `token_ids`
+    #[payable]
+    pub fn nft_unstake(&mut self, token_ids: Vec<TokenId>) -> U128 {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+
+        let mut staked_token_ids = self.staked_tokens_per_owner.get(&owner_id).expect("Marble: no staked tokens");
+        let mut points: u128 = 0;
+        for token_id in token_ids.iter() {
+            let mut stake = self.stakes_by_id.remove(token_id).expect("Marble: token is not staked");
+            assert_eq!(stake.owner_id, owner_id, "Token owner only");
+            points = points.saturating_add(accrue_points(&mut stake, env::block_timestamp()));
+            staked_token_ids.remove(token_id);
+        }
+        if staked_token_ids.is_empty() {
+            self.staked_tokens_per_owner.remove(&owner_id);
+        } else {
+            self.staked_tokens_per_owner.insert(&owner_id, &staked_token_ids);
+        }
+        self.internal_add_points(&owner_id, points);
+
+        env::log(
+            json!({
+                "type": "nft_unstake",
+                "params": {
+                    "owner_id": owner_id,
+                    "token_ids": token_ids,
+                    "points": U128::from(points),
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+
+        U128::from(points)
+    }
+
+    /// Credits the points accrued by all tokens the caller has staked. Returns the points added.
+    #[payable]
+    pub fn claim_points(&mut self) -> U128 {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+
+        let mut points: u128 = 0;
+        if let Some(staked_token_ids) = self.staked_tokens_per_owner.get(&owner_id) {
+            for token_id in staked_token_ids.iter() {
+                let mut stake = self.stakes_by_id.get(&token_id).unwrap();
+                points = points.saturating_add(accrue_points(&mut stake, env::block_timestamp()));
+                self.stakes_by_id.insert(&token_id, &stake);
+            }
+        }
+        self.internal_add_points(&owner_id, points);
+
+        env::log(
+            json!({
+                "type": "claim_points",
+                "params": {
+                    "owner_id": owner_id,
+                    "points": U128::from(points),
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+
+        U128::from(points)
+    }
+
+    /// Claimed points, not counting what staked tokens accrued since the last claim
+    pub fn nft_points_of(&self, account_id: ValidAccountId) -> U128 {
+        self.points_by_account.get(account_id.as_ref()).unwrap_or(0).into()
+    }
+
+    pub fn nft_pending_points_of(&self, account_id: ValidAccountId) -> U128 {
+        self.staked_tokens_per_owner
+            .get(account_id.as_ref())
+            .map(|staked_token_ids| {
+                staked_token_ids
+                    .iter()
+                    .filter_map(|token_id| self.stakes_by_id.get(&token_id))
+                    .map(|mut stake| accrue_points(&mut stake, env::block_timestamp()))
+                    .fold(0, u128::saturating_add)
+            })
+            .unwrap_or(0)
+            .into()
+    }
+
+    pub fn nft_stake_of(&self, token_id: TokenId) -> Option<StakeJson> {
+        self.stakes_by_id.get(&token_id).map(|stake| stake_to_json(token_id, stake))
+    }
+
+    pub fn nft_staked_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<StakeJson> {
+        let staked_token_ids = match self.staked_tokens_per_owner.get(account_id.as_ref()) {
+            Some(staked_token_ids) => staked_token_ids,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), staked_token_ids.len());
+
+        let token_vector = staked_token_ids.as_vector();
+        (start_index..end_index)
+            .filter_map(|index| token_vector.get(index))
+            .filter_map(|token_id| self.nft_stake_of(token_id))
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_not_staked(&self, token_id: &TokenId) {
+        assert!(self.stakes_by_id.get(token_id).is_none(), "Marble: token is staked");
+    }
+
+    fn internal_add_points(&mut self, account_id: &AccountId, points: u128) {
+        if points == 0 {
+            return;
+        }
+        let balance = self.points_by_account.get(account_id).unwrap_or(0);
+        self.points_by_account.insert(account_id, &balance.saturating_add(points));
+    }
+}
+
+/// Points for the whole seconds elapsed since `stake.since`, which moves forward by those seconds.
+/// Balances stop at u128::MAX instead of overflowing on a very high rate.
+fn accrue_points(stake: &mut Stake, now: u64) -> u128 {
+    let seconds = now.saturating_sub(stake.since) / NANOSECONDS_PER_SECOND;
+    stake.since += seconds * NANOSECONDS_PER_SECOND;
+    (seconds as u128).saturating_mul(stake.points_per_second)
+}
+
+fn stake_to_json(token_id: TokenId, mut stake: Stake) -> StakeJson {
+    let since = stake.since;
+    let pending_points = accrue_points(&mut stake, env::block_timestamp());
+    StakeJson {
+        token_id,
+        owner_id: stake.owner_id,
+        since: U64::from(since),
+        points_per_second: U128::from(stake.points_per_second),
+        pending_points: U128::from(pending_points),
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{mint_token, setup_series, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    const START: u64 = 1_000 * NANOSECONDS_PER_SECOND;
+
+    /// accounts(2) stakes two tokens of a series earning 10 points per second at `START`
+    fn setup_staked() -> (VMContextBuilder, Contract, Vec<TokenId>) {
+        let (mut context, mut contract) = setup_series();
+        contract.nft_set_series_staking_rate("1".to_string(), U128::from(10));
+
+        let token_ids: Vec<TokenId> = (0..2)
+            .map(|_| mint_token(&mut context, &mut contract, "1", accounts(2)))
+            .collect();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(START)
+            .build()
+        );
+        contract.nft_stake(token_ids.clone());
+
+        (context, contract, token_ids)
+    }
+
+    #[test]
+    fn test_stake_claim_unstake() {
+        let (mut context, mut contract, token_ids) = setup_staked();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(START + 100 * NANOSECONDS_PER_SECOND + 500_000_000)
+            .build()
+        );
+        assert_eq!(contract.nft_pending_points_of(accounts(2)), U128::from(2_000));
+        assert_eq!(contract.claim_points(), U128::from(2_000));
+        assert_eq!(contract.nft_points_of(accounts(2)), U128::from(2_000));
+        assert_eq!(contract.nft_pending_points_of(accounts(2)), U128::from(0));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(START + 150 * NANOSECONDS_PER_SECOND)
+            .build()
+        );
+        assert_eq!(contract.nft_unstake(vec![token_ids[0].clone()]), U128::from(500));
+        assert_eq!(contract.nft_points_of(accounts(2)), U128::from(2_500));
+        assert_eq!(contract.nft_staked_tokens_for_owner(accounts(2), None, None).len(), 1);
+
+        contract.nft_transfer(accounts(3), token_ids[0].clone(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: token is staked")]
+    fn test_invalid_transfer_staked() {
+        let (mut context, mut contract, token_ids) = setup_staked();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_transfer(accounts(3), token_ids[0].clone(), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: token is staked")]
+    fn test_invalid_approve_staked() {
+        let (mut context, mut contract, token_ids) = setup_staked();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_approve(token_ids[0].clone(), accounts(3), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: revocable tokens can't be staked")]
+    fn test_invalid_stake_revocable_soulbound() {
+        let (mut context, mut contract) = setup_series();
+        contract.nft_set_series_staking_rate("1".to_string(), U128::from(10));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_set_series_transferability("1".to_string(), Transferability::RevocableSoulbound);
+        let token_id = mint_token(&mut context, &mut contract, "1", accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_stake(vec![token_id]);
+    }
+
+    #[test]
+    fn test_points_saturate_at_max_rate() {
+        let (mut context, mut contract) = setup_series();
+        contract.nft_set_series_staking_rate("1".to_string(), U128::from(u128::MAX));
+        let token_ids: Vec<TokenId> = (0..2)
+            .map(|_| mint_token(&mut context, &mut contract, "1", accounts(2)))
+            .collect();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .block_timestamp(START)
+            .build()
+        );
+        contract.nft_stake(token_ids.clone());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(START + 2 * NANOSECONDS_PER_SECOND)
+            .build()
+        );
+        assert_eq!(contract.nft_pending_points_of(accounts(2)), U128::from(u128::MAX));
+        assert_eq!(contract.nft_unstake(token_ids), U128::from(u128::MAX));
+        assert_eq!(contract.nft_points_of(accounts(2)), U128::from(u128::MAX));
+    }
+}
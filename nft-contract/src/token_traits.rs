@@ -0,0 +1,299 @@
+use crate::*;
+
+/// trait_type -> value
+pub type Traits = HashMap<String, String>;
+
+const MAX_TRAITS_PER_TOKEN: usize = 20;
+const MAX_TRAIT_BYTES: usize = 64;
+
+#[near_bindgen]
+impl Contract {
+    /// Traits every token minted afterwards in the series starts with. Traits passed to
+    /// `nft_mint` override these per token.
+    #[payable]
+    pub fn nft_set_series_traits(&mut self, token_series_id: TokenSeriesId, traits: Traits) {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+
+        let token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert_valid_traits(&traits);
+
+        if traits.is_empty() {
+            self.series_traits.remove(&token_series_id);
+        } else {
+            self.series_traits.insert(&token_series_id, &traits);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_set_series_traits",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "traits": traits,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+    }
+
+    pub fn nft_get_series_traits(&self, token_series_id: TokenSeriesId) -> Option<Traits> {
+        self.series_traits.get(&token_series_id)
+    }
+
+    pub fn nft_tokens_with_trait(
+        &self,
+        token_series_id: TokenSeriesId,
+        trait_type: String,
+        value: String,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenJson> {
+        let token_ids = match self.tokens_by_trait.get(&trait_key(&token_series_id, &trait_type, &value)) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), token_ids.len());
+
+        let token_vector = token_ids.as_vector();
+        (start_index..end_index)
+            .filter_map(|index| token_vector.get(index))
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+
+    /// Number of live tokens per trait_type and value, for rarity. Pages over the distinct
+    /// (trait_type, value) pairs of the series.
+    pub fn nft_trait_counts(
+        &self,
+        token_series_id: TokenSeriesId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> HashMap<String, HashMap<String, U64>> {
+        let mut trait_counts: HashMap<String, HashMap<String, U64>> = HashMap::new();
+        let trait_values = match self.trait_values_by_series.get(&token_series_id) {
+            Some(trait_values) => trait_values,
+            None => return trait_counts,
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), trait_values.len());
+
+        let trait_vector = trait_values.as_vector();
+        for (trait_type, value) in (start_index..end_index).filter_map(|index| trait_vector.get(index)) {
+            let count = self.internal_trait_count(&token_series_id, &trait_type, &value);
+            trait_counts.entry(trait_type).or_insert_with(HashMap::new).insert(value, U64::from(count));
+        }
+        trait_counts
+    }
+
+    pub fn nft_trait_count(&self, token_series_id: TokenSeriesId, trait_type: String, value: String) -> U64 {
+        self.internal_trait_count(&token_series_id, &trait_type, &value).into()
+    }
+}
+
+impl Contract {
+    /// Stores the series traits overlaid with `traits` for a freshly minted token and indexes them
+    pub(crate) fn internal_set_token_traits(
+        &mut self,
+        token_series_id: &TokenSeriesId,
+        token_id: &TokenId,
+        traits: Option<Traits>,
+    ) {
+        let mut token_traits = self.series_traits.get(token_series_id).unwrap_or_default();
+        if let Some(traits) = traits {
+            assert_valid_traits(&traits);
+            token_traits.extend(traits);
+        }
+        if token_traits.is_empty() {
+            return;
+        }
+
+        let mut trait_values = self.trait_values_by_series.get(token_series_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::TraitValuesBySeriesInner {
+                token_series: token_series_id.clone(),
+            })
+        });
+        for (trait_type, value) in token_traits.iter() {
+            let key = trait_key(token_series_id, trait_type, value);
+            let mut token_ids = self.tokens_by_trait.get(&key).unwrap_or_else(|| {
+                UnorderedSet::new(StorageKey::TokensByTraitInner {
+                    trait_hash: key.clone(),
+                })
+            });
+            token_ids.insert(token_id);
+            self.tokens_by_trait.insert(&key, &token_ids);
+            trait_values.insert(&(trait_type.clone(), value.clone()));
+        }
+        self.trait_values_by_series.insert(token_series_id, &trait_values);
+        self.token_traits_by_id.insert(token_id, &token_traits);
+    }
+
+    pub(crate) fn internal_remove_token_traits(&mut self, token_id: &TokenId) {
+        let token_traits = match self.token_traits_by_id.remove(token_id) {
+            Some(token_traits) => token_traits,
+            None => return,
+        };
+        let token_series_id = get_token_series_id(token_id);
+
+        let mut trait_values = match self.trait_values_by_series.get(&token_series_id) {
+            Some(trait_values) => trait_values,
+            None => return,
+        };
+        for (trait_type, value) in token_traits.iter() {
+            let key = trait_key(&token_series_id, trait_type, value);
+            if let Some(mut token_ids) = self.tokens_by_trait.get(&key) {
+                token_ids.remove(token_id);
+                if token_ids.is_empty() {
+                    self.tokens_by_trait.remove(&key);
+                    trait_values.remove(&(trait_type.clone(), value.clone()));
+                } else {
+                    self.tokens_by_trait.insert(&key, &token_ids);
+                }
+            }
+        }
+        if trait_values.is_empty() {
+            self.trait_values_by_series.remove(&token_series_id);
+        } else {
+            self.trait_values_by_series.insert(&token_series_id, &trait_values);
+        }
+    }
+
+    fn internal_trait_count(&self, token_series_id: &TokenSeriesId, trait_type: &str, value: &str) -> u64 {
+        self.tokens_by_trait
+            .get(&trait_key(token_series_id, trait_type, value))
+            .map(|token_ids| token_ids.len())
+            .unwrap_or(0)
+    }
+}
+
+/// Hash of the length-prefixed (series, trait_type, value), so no delimiter can collide
+fn trait_key(token_series_id: &TokenSeriesId, trait_type: &str, value: &str) -> Vec<u8> {
+    let key = (token_series_id.clone(), trait_type.to_string(), value.to_string());
+    env::sha256(&key.try_to_vec().unwrap())
+}
+
+fn assert_valid_traits(traits: &Traits) {
+    assert!(
+        traits.len() <= MAX_TRAITS_PER_TOKEN,
+        "Marble: traits exceed {} entries",
+        MAX_TRAITS_PER_TOKEN
+    );
+    for (trait_type, value) in traits.iter() {
+        assert!(!trait_type.is_empty(), "Marble: trait_type is required");
+        assert!(
+            trait_type.len() <= MAX_TRAIT_BYTES && value.len() <= MAX_TRAIT_BYTES,
+            "Marble: trait_type and value are limited to {} bytes",
+            MAX_TRAIT_BYTES
+        );
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{setup_series, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn make_traits(pairs: Vec<(&str, &str)>) -> Traits {
+        pairs.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_mint_with_traits() {
+        let (mut context, mut contract) = setup_series();
+        contract.nft_set_series_traits(
+            "1".to_string(),
+            make_traits(vec![("background", "blue"), ("class", "warrior")]),
+        );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT * 2)
+            .build()
+        );
+
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, None);
+        let rare_token_id = contract.nft_mint(
+            "1".to_string(),
+            accounts(2),
+            None,
+            None,
+            Some(make_traits(vec![("background", "gold")])),
+        );
+
+        let token = contract.nft_token(rare_token_id.clone()).unwrap();
+        assert_eq!(
+            token.traits.unwrap(),
+            make_traits(vec![("background", "gold"), ("class", "warrior")])
+        );
+
+        let tokens = contract.nft_tokens_with_trait(
+            "1".to_string(),
+            "background".to_string(),
+            "gold".to_string(),
+            None,
+            None,
+        );
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_id, rare_token_id);
+
+        let counts = contract.nft_trait_counts("1".to_string(), None, None);
+        assert_eq!(counts["background"]["blue"], U64::from(1));
+        assert_eq!(counts["background"]["gold"], U64::from(1));
+        assert_eq!(counts["class"]["warrior"], U64::from(2));
+        let page = contract.nft_trait_counts("1".to_string(), Some(U128::from(1)), Some(1));
+        assert_eq!(page.values().map(|values| values.len()).sum::<usize>(), 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_burn(token_id);
+
+        let counts = contract.nft_trait_counts("1".to_string(), None, None);
+        assert!(counts["background"].get("blue").is_none());
+        assert_eq!(counts["class"]["warrior"], U64::from(1));
+        assert!(contract
+            .nft_tokens_with_trait("1".to_string(), "background".to_string(), "blue".to_string(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_trait_keys_with_delimiter_do_not_collide() {
+        let (mut context, mut contract) = setup_series();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT * 2)
+            .build()
+        );
+
+        contract.nft_mint("1".to_string(), accounts(2), None, None, Some(make_traits(vec![("a:b", "c")])));
+        let token_id = contract.nft_mint("1".to_string(), accounts(2), None, None, Some(make_traits(vec![("a", "b:c")])));
+
+        let tokens = contract.nft_tokens_with_trait("1".to_string(), "a".to_string(), "b:c".to_string(), None, None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_id, token_id);
+        assert_eq!(contract.nft_trait_count("1".to_string(), "a:b".to_string(), "c".to_string()), U64::from(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: trait_type and value are limited to 64 bytes")]
+    fn test_invalid_trait_value_too_long() {
+        let (_, mut contract) = setup_series();
+        contract.nft_set_series_traits("1".to_string(), make_traits(vec![("background", &"a".repeat(65))]));
+    }
+}
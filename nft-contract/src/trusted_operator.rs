@@ -0,0 +1,343 @@
+use crate::*;
+
+/// Contracts allowlisted by the owner can move tokens without an attached deposit, but only
+/// for holders who opted in to them
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn add_trusted_operator(&mut self, operator_id: ValidAccountId) {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Only owner"
+        );
+        let initial_storage_usage = env::storage_usage();
+        if self.trusted_operators.insert(operator_id.as_ref()) {
+            let version = self.trusted_operator_versions.get(operator_id.as_ref()).unwrap_or(0) + 1;
+            self.trusted_operator_versions.insert(operator_id.as_ref(), &version);
+        }
+
+        env::log(
+            json!({
+                "type": "add_trusted_operator",
+                "params": {
+                    "operator_id": operator_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage() - initial_storage_usage, 0);
+    }
+
+    /// Holder opt-ins made before removal stay void if the operator is added again
+    #[payable]
+    pub fn remove_trusted_operator(&mut self, operator_id: ValidAccountId) {
+        assert_one_yocto();
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Marble: Only owner"
+        );
+        self.trusted_operators.remove(operator_id.as_ref());
+
+        env::log(
+            json!({
+                "type": "remove_trusted_operator",
+                "params": {
+                    "operator_id": operator_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+    }
+
+    pub fn get_trusted_operators(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), self.trusted_operators.len());
+
+        let operators = self.trusted_operators.as_vector();
+        (start_index..end_index)
+            .filter_map(|index| operators.get(index))
+            .collect()
+    }
+
+    /// Lets a trusted operator move the caller's tokens, or only those of `token_series_id`.
+    /// Opting in again replaces the scope.
+    #[payable]
+    pub fn nft_opt_in_trusted_operator(&mut self, operator_id: ValidAccountId, token_series_id: Option<TokenSeriesId>) {
+        assert!(env::attached_deposit() >= 1, "Marble: requires attached deposit of at least 1 yoctoNEAR");
+        let initial_storage_usage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        assert!(
+            self.trusted_operators.contains(operator_id.as_ref()),
+            "Marble: operator is not trusted"
+        );
+        if let Some(token_series_id) = &token_series_id {
+            assert!(
+                self.token_series_by_id.get(token_series_id).is_some(),
+                "Marble: token series {} not exist",
+                token_series_id
+            );
+        }
+
+        let mut opt_ins = self.trusted_operator_opt_ins.get(&account_id).unwrap_or_else(|| {
+            UnorderedMap::new(StorageKey::TrustedOperatorOptInsInner {
+                account_hash: env::sha256(account_id.as_bytes()),
+            })
+        });
+        let version = self.trusted_operator_versions.get(operator_id.as_ref()).unwrap_or(0);
+        opt_ins.insert(operator_id.as_ref(), &(token_series_id.clone(), version));
+        self.trusted_operator_opt_ins.insert(&account_id, &opt_ins);
+
+        env::log(
+            json!({
+                "type": "nft_opt_in_trusted_operator",
+                "params": {
+                    "account_id": account_id,
+                    "operator_id": operator_id,
+                    "token_series_id": token_series_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage), 0);
+    }
+
+    /// Refunds the released storage
+    #[payable]
+    pub fn nft_opt_out_trusted_operator(&mut self, operator_id: ValidAccountId) {
+        assert_one_yocto();
+        let initial_storage_usage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+
+        let mut opt_ins = match self.trusted_operator_opt_ins.get(&account_id) {
+            Some(opt_ins) => opt_ins,
+            None => return,
+        };
+        opt_ins.remove(operator_id.as_ref());
+        if opt_ins.is_empty() {
+            self.trusted_operator_opt_ins.remove(&account_id);
+        } else {
+            self.trusted_operator_opt_ins.insert(&account_id, &opt_ins);
+        }
+
+        env::log(
+            json!({
+                "type": "nft_opt_out_trusted_operator",
+                "params": {
+                    "account_id": account_id,
+                    "operator_id": operator_id,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+
+        refund_released_storage(&account_id, initial_storage_usage);
+    }
+
+    /// Whether `operator_id` is trusted and `account_id` opted in to it for `token_series_id`
+    pub fn nft_is_trusted_operator_for(
+        &self,
+        account_id: ValidAccountId,
+        operator_id: ValidAccountId,
+        token_series_id: TokenSeriesId,
+    ) -> bool {
+        self.internal_is_trusted_operator_for(account_id.as_ref(), operator_id.as_ref(), &token_series_id)
+    }
+
+    /// Transfer by a trusted operator, no deposit required. Logs an operator-initiated
+    /// `nft_operator_transfer` next to the NEP-171 transfer.
+    pub fn nft_operator_transfer(&mut self, receiver_id: ValidAccountId, token_id: TokenId, memo: Option<String>) {
+        let operator_id = env::predecessor_account_id();
+        assert!(
+            self.trusted_operators.contains(&operator_id),
+            "Marble: caller is not a trusted operator"
+        );
+        let owner_id = self.tokens.owner_by_id.get(&token_id).expect("Token not found");
+        assert!(
+            self.internal_is_trusted_operator_for(&owner_id, &operator_id, &get_token_series_id(&token_id)),
+            "Marble: owner has not opted in to operator"
+        );
+
+        let receiver_id_str = receiver_id.to_string();
+        self.internal_transfer(&owner_id, &receiver_id_str, &token_id, None, memo.clone());
+
+        env::log(
+            json!({
+                "type": "nft_operator_transfer",
+                "params": {
+                    "operator_id": operator_id,
+                    "owner_id": owner_id,
+                    "receiver_id": receiver_id_str,
+                    "token_id": token_id,
+                    "memo": memo,
+                    "operator_initiated": true,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+        NearEvent::log_nft_transfer(
+            owner_id,
+            receiver_id_str,
+            vec![token_id],
+            memo,
+            Some(operator_id),
+        );
+    }
+}
+
+impl Contract {
+    fn internal_is_trusted_operator_for(
+        &self,
+        account_id: &AccountId,
+        operator_id: &AccountId,
+        token_series_id: &TokenSeriesId,
+    ) -> bool {
+        if !self.trusted_operators.contains(operator_id) {
+            return false;
+        }
+        let version = self.trusted_operator_versions.get(operator_id).unwrap_or(0);
+        match self.trusted_operator_opt_ins.get(account_id).and_then(|opt_ins| opt_ins.get(operator_id)) {
+            Some((_, opt_in_version)) if opt_in_version != version => false,
+            Some((Some(scope), _)) => &scope == token_series_id,
+            Some((None, _)) => true,
+            None => false,
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{setup_token, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    /// accounts(2) holds a token of series "1", accounts(4) is the trusted game server
+    fn setup_operator() -> (VMContextBuilder, Contract, TokenId) {
+        let (context, mut contract, token_id) = setup_token();
+        contract.add_trusted_operator(accounts(4));
+
+        (context, contract, token_id)
+    }
+
+    #[test]
+    fn test_nft_operator_transfer() {
+        let (mut context, mut contract, token_id) = setup_operator();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_opt_in_trusted_operator(accounts(4), Some("1".to_string()));
+        assert!(contract.nft_is_trusted_operator_for(accounts(2), accounts(4), "1".to_string()));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(0)
+            .build()
+        );
+        contract.nft_operator_transfer(accounts(3), token_id.clone(), None);
+
+        let token = contract.nft_token(token_id).unwrap();
+        assert_eq!(token.owner_id, accounts(3).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: caller is not a trusted operator")]
+    fn test_invalid_operator_transfer_unregistered() {
+        let (mut context, mut contract, token_id) = setup_operator();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(0)
+            .build()
+        );
+        contract.nft_operator_transfer(accounts(3), token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: owner has not opted in to operator")]
+    fn test_invalid_operator_transfer_without_opt_in() {
+        let (mut context, mut contract, token_id) = setup_operator();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(0)
+            .build()
+        );
+        contract.nft_operator_transfer(accounts(3), token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: caller is not a trusted operator")]
+    fn test_invalid_operator_transfer_after_removal() {
+        let (mut context, mut contract, token_id) = setup_operator();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_opt_in_trusted_operator(accounts(4), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.remove_trusted_operator(accounts(4));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(0)
+            .build()
+        );
+        contract.nft_operator_transfer(accounts(3), token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: owner has not opted in to operator")]
+    fn test_invalid_operator_transfer_after_readd() {
+        let (mut context, mut contract, token_id) = setup_operator();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_opt_in_trusted_operator(accounts(4), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.remove_trusted_operator(accounts(4));
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.add_trusted_operator(accounts(4));
+        assert!(!contract.nft_is_trusted_operator_for(accounts(2), accounts(4), "1".to_string()));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(0)
+            .build()
+        );
+        contract.nft_operator_transfer(accounts(3), token_id, None);
+    }
+}
@@ -0,0 +1,329 @@
+use crate::*;
+
+/// Timestamps are nanoseconds, like `issued_at`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum ValidityWindow {
+    /// Same window for every token of the series
+    Absolute {
+        starts_at: Option<U64>,
+        expires_at: Option<U64>,
+    },
+    /// Starts `starts_after` past mint time and lasts `duration`
+    Relative {
+        starts_after: Option<U64>,
+        duration: Option<U64>,
+    },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SeriesValidity {
+    pub window: ValidityWindow,
+    /// Expired tokens can't be transferred or approved
+    #[serde(default)]
+    pub block_transfer_when_expired: bool,
+    /// Anyone can burn an expired token with `nft_burn_expired`
+    #[serde(default)]
+    pub burnable_when_expired: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Window stamped into `starts_at` / `expires_at` of minted tokens, with the transfer and burn
+    /// policies for them. Can't be changed once the series has minted tokens.
+    #[payable]
+    pub fn nft_set_series_validity(&mut self, token_series_id: TokenSeriesId, validity: Option<SeriesValidity>) {
+        assert_one_yocto();
+
+        let mut token_series = self.token_series_by_id.get(&token_series_id).expect("Token series not exist");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token_series.creator_id,
+            "Marble: Creator only"
+        );
+        assert!(token_series.tokens.is_empty(), "Marble: series already has minted tokens");
+
+        token_series.validity = validity.clone();
+        self.token_series_by_id.insert(&token_series_id, &token_series);
+        env::log(
+            json!({
+                "type": "nft_set_series_validity",
+                "params": {
+                    "token_series_id": token_series_id,
+                    "validity": validity,
+                }
+            })
+                .to_string()
+                .as_bytes(),
+        );
+    }
+
+    #[payable]
+    pub fn nft_burn_expired(&mut self, token_id: TokenId) {
+        assert_one_yocto();
+
+        let token_series = self.token_series_by_id.get(&get_token_series_id(&token_id)).expect("Token series not exist");
+        assert!(
+            token_series.validity.map(|validity| validity.burnable_when_expired).unwrap_or(false),
+            "Marble: expired tokens of this series are not burnable"
+        );
+        let owner_id = self.tokens.owner_by_id.get(&token_id).expect("Token not found");
+        assert!(self.internal_is_expired(&token_id), "Marble: token is not expired");
+
+        self.internal_burn(&token_id, &owner_id);
+
+        NearEvent::log_nft_burn(
+            owner_id,
+            vec![token_id],
+            Some("expired".to_string()),
+            Some(env::predecessor_account_id()),
+        );
+    }
+
+    /// False for missing tokens and outside the token's starts_at / expires_at
+    pub fn nft_is_valid(&self, token_id: TokenId) -> bool {
+        if self.tokens.owner_by_id.get(&token_id).is_none() {
+            return false;
+        }
+        let token_metadata = self.internal_get_token_metadata(&token_id);
+        let now = env::block_timestamp();
+        parse_timestamp(&token_metadata.starts_at).map(|starts_at| now >= starts_at).unwrap_or(true)
+            && parse_timestamp(&token_metadata.expires_at).map(|expires_at| now < expires_at).unwrap_or(true)
+    }
+
+    /// Pages over all tokens of `account_id` like `nft_tokens_for_owner` and keeps the valid ones,
+    /// so a page can hold fewer than `limit` tokens
+    pub fn nft_tokens_valid_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<TokenJson> {
+        let token_set = match self.tokens.tokens_per_owner.as_ref().and_then(|by_owner| by_owner.get(account_id.as_ref())) {
+            Some(token_set) => token_set,
+            None => return vec![],
+        };
+        let start_index: u64 = from_index.map(|v| v.0 as u64).unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let end_index = std::cmp::min(start_index.saturating_add(limit), token_set.len());
+
+        let token_vector = token_set.as_vector();
+        (start_index..end_index)
+            .filter_map(|index| token_vector.get(index))
+            .filter(|token_id| self.nft_is_valid(token_id.clone()))
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_is_expired(&self, token_id: &TokenId) -> bool {
+        parse_timestamp(&self.internal_get_token_metadata(token_id).expires_at)
+            .map(|expires_at| env::block_timestamp() >= expires_at)
+            .unwrap_or(false)
+    }
+}
+
+/// Fills the token's starts_at / expires_at from the series window unless it brings its own
+pub(crate) fn stamp_validity(mut token_metadata: TokenMetadata, window: &ValidityWindow, minted_at: u64) -> TokenMetadata {
+    let (starts_at, expires_at) = match window {
+        ValidityWindow::Absolute { starts_at, expires_at } => (starts_at.map(|v| v.0), expires_at.map(|v| v.0)),
+        ValidityWindow::Relative { starts_after, duration } => {
+            let starts_at = minted_at.saturating_add(starts_after.map(|v| v.0).unwrap_or(0));
+            (
+                starts_after.map(|_| starts_at),
+                duration.map(|duration| starts_at.saturating_add(duration.0)),
+            )
+        }
+    };
+    if token_metadata.starts_at.is_none() {
+        token_metadata.starts_at = starts_at.map(|v| v.to_string());
+    }
+    if token_metadata.expires_at.is_none() {
+        token_metadata.expires_at = expires_at.map(|v| v.to_string());
+    }
+    token_metadata
+}
+
+/// Values that aren't nanosecond timestamps (e.g. ISO 8601 set by hand) are not enforced
+fn parse_timestamp(value: &Option<String>) -> Option<u64> {
+    value.as_ref().and_then(|value| value.parse().ok())
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::tests::{mint_token, setup_series, STORAGE_FOR_MINT};
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn setup_ticket(validity: SeriesValidity) -> (VMContextBuilder, Contract, TokenId) {
+        let (mut context, mut contract) = setup_series();
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .block_timestamp(1_000)
+            .build()
+        );
+        contract.nft_set_series_validity("1".to_string(), Some(validity));
+
+        let token_id = mint_token(&mut context, &mut contract, "1", accounts(2));
+
+        (context, contract, token_id)
+    }
+
+    #[test]
+    fn test_relative_validity() {
+        let (mut context, mut contract, token_id) = setup_ticket(SeriesValidity {
+            window: ValidityWindow::Relative {
+                starts_after: Some(U64::from(100)),
+                duration: Some(U64::from(500)),
+            },
+            block_transfer_when_expired: false,
+            burnable_when_expired: true,
+        });
+
+        let token_metadata = contract.nft_token(token_id.clone()).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.starts_at, Some("1100".to_string()));
+        assert_eq!(token_metadata.expires_at, Some("1600".to_string()));
+
+        assert!(!contract.nft_is_valid(token_id.clone()));
+
+        testing_env!(context.block_timestamp(1_100).build());
+        assert!(contract.nft_is_valid(token_id.clone()));
+        assert_eq!(contract.nft_tokens_valid_for_owner(accounts(2), None, None).len(), 1);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .block_timestamp(1_600)
+            .build()
+        );
+        assert!(!contract.nft_is_valid(token_id.clone()));
+        assert!(contract.nft_tokens_valid_for_owner(accounts(2), None, None).is_empty());
+
+        contract.nft_burn_expired(token_id.clone());
+        assert!(contract.nft_token(token_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: token is expired")]
+    fn test_invalid_transfer_expired() {
+        let (mut context, mut contract, token_id) = setup_ticket(SeriesValidity {
+            window: ValidityWindow::Absolute {
+                starts_at: None,
+                expires_at: Some(U64::from(2_000)),
+            },
+            block_transfer_when_expired: true,
+            burnable_when_expired: false,
+        });
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(1_500)
+            .build()
+        );
+        contract.nft_transfer(accounts(3), token_id.clone(), None, None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .block_timestamp(2_000)
+            .build()
+        );
+        contract.nft_transfer(accounts(2), token_id, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: token is expired")]
+    fn test_invalid_transfer_expired_after_metadata_change() {
+        let (mut context, mut contract, token_id) = setup_ticket(SeriesValidity {
+            window: ValidityWindow::Absolute {
+                starts_at: None,
+                expires_at: Some(U64::from(2_000)),
+            },
+            block_transfer_when_expired: true,
+            burnable_when_expired: false,
+        });
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(1_500)
+            .build()
+        );
+        let mut metadata = contract.nft_token(token_id.clone()).unwrap().metadata.unwrap();
+        metadata.expires_at = Some(u64::MAX.to_string());
+        contract.nft_change_metadata(token_id.clone(), metadata);
+        let token_metadata = contract.nft_token(token_id.clone()).unwrap().metadata.unwrap();
+        assert_eq!(token_metadata.expires_at, Some("2000".to_string()));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(1)
+            .block_timestamp(2_000)
+            .build()
+        );
+        contract.nft_transfer(accounts(3), token_id, None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: series already has minted tokens")]
+    fn test_invalid_series_validity_after_mint() {
+        let (mut context, mut contract, _) = setup_ticket(SeriesValidity {
+            window: ValidityWindow::Absolute {
+                starts_at: None,
+                expires_at: Some(U64::from(2_000)),
+            },
+            block_transfer_when_expired: false,
+            burnable_when_expired: false,
+        });
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build()
+        );
+        contract.nft_set_series_validity("1".to_string(), Some(SeriesValidity {
+            window: ValidityWindow::Absolute {
+                starts_at: None,
+                expires_at: Some(U64::from(2_000)),
+            },
+            block_transfer_when_expired: false,
+            burnable_when_expired: true,
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Marble: token is attached to another token")]
+    fn test_invalid_burn_expired_attached() {
+        let (mut context, mut contract, token_id) = setup_ticket(SeriesValidity {
+            window: ValidityWindow::Absolute {
+                starts_at: None,
+                expires_at: Some(U64::from(2_000)),
+            },
+            block_transfer_when_expired: false,
+            burnable_when_expired: true,
+        });
+        let parent_token_id = mint_token(&mut context, &mut contract, "1", accounts(2));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(STORAGE_FOR_MINT)
+            .build()
+        );
+        contract.nft_attach(parent_token_id, token_id.clone());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(1)
+            .block_timestamp(2_000)
+            .build()
+        );
+        contract.nft_burn_expired(token_id);
+    }
+}
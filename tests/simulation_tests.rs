@@ -8,10 +8,16 @@ pub const NFT_CONTRACT_ID: &str = "nft";
 
 near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
     NFT_WASM_BYTES => "out/main.wasm",
+    SHARE_WASM_BYTES => "out/fraction_share.wasm",
+    GATE_WASM_BYTES => "out/token_gate.wasm",
+    LEGACY_NFT_WASM_BYTES => "out/main_legacy.wasm",
 }
 
-// Added after running simulation test -> with max token series id and 64 byte account
-pub const STORAGE_MINT_ESTIMATE: u128 = 11280000000000000000000;
+// Added after running simulation test -> with max token series id and 64 byte account:
+// 0.01128 for the full TokenMetadata layout (simulate_mint_compact_token_data measures the compact record against it),
+// plus the account's first entry in the per-series owner index (0.00366) and the tree the
+// account's first token creates in the per-owner ordered index (about 0.0056, sized from the entry layout)
+pub const STORAGE_MINT_ESTIMATE: u128 = 20290000000000000000000;
 pub const STORAGE_CREATE_SERIES_ESTIMATE: u128 = 8540000000000000000000;
 pub const STORAGE_APPROVE: u128 = 2610000000000000000000;
 
@@ -295,4 +301,487 @@ fn simulate_create_mint_bundle() {
         (nft.account().unwrap().storage_usage - initial_storage_usage) as u128 * 10u128.pow(19);
     println!("[CREATE MINT BUNDLE] Storage price: {} yoctoNEAR", storage_price_for_adding_series);
     println!("[CREATE MINT BUNDLE] Gas burnt price: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
-}
\ No newline at end of file
+}
+
+#[test]
+fn simulate_buy_primary_split() {
+    let (root, nft, treasury) = init();
+
+    let alice = root.create_user("alice".to_string(), to_yocto("100"));
+    let bob = root.create_user("bob".to_string(), to_yocto("100"));
+    let carol = root.create_user("carol".to_string(), to_yocto("100"));
+
+    root.call(
+        nft.account_id(),
+        "nft_create_series",
+        &json!({
+            "token_metadata": {
+                "title": "A".repeat(200),
+                "reference": "A".repeat(59),
+                "media": "A".repeat(59),
+                "copies": 100u64,
+            },
+            "price": (to_yocto("1") + 7).to_string(),
+            "creator_id": alice.account_id(),
+            "primary_split": {
+                alice.account_id(): 3333u32,
+                bob.account_id(): 3333u32,
+                carol.account_id(): 3334u32,
+            },
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("1")
+    ).assert_success();
+
+    let treasury_balance = treasury.account().unwrap().amount;
+    let alice_balance = alice.account().unwrap().amount;
+    let bob_balance = bob.account().unwrap().amount;
+    let carol_balance = carol.account().unwrap().amount;
+
+    root.call(
+        nft.account_id(),
+        "nft_buy",
+        &json!({
+            "token_series_id": "1",
+            "receiver_id": root.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("1") + 7 + STORAGE_MINT_ESTIMATE
+    ).assert_success();
+
+    let diff_treasury = treasury.account().unwrap().amount - treasury_balance;
+    let diff_alice = alice.account().unwrap().amount - alice_balance;
+    let diff_bob = bob.account().unwrap().amount - bob_balance;
+    let diff_carol = carol.account().unwrap().amount - carol_balance;
+
+    assert_eq!((to_yocto("1") + 7) * 500 / 10_000, diff_treasury);
+    assert_eq!(diff_alice, diff_bob);
+    assert_eq!(diff_treasury + diff_alice + diff_bob + diff_carol, to_yocto("1") + 7);
+}
+
+#[test]
+fn simulate_cursor_pagination_gas() {
+    let (root, nft, _) = init();
+
+    root.call(
+        nft.account_id(),
+        "nft_create_series",
+        &json!({
+            "token_metadata": {
+                "title": "A".repeat(200),
+                "reference": "A".repeat(59),
+                "media": "A".repeat(59),
+            },
+            "creator_id": root.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("1")
+    ).assert_success();
+
+    let total_tokens = 10_000;
+    for _ in 0..total_tokens {
+        root.call(
+            nft.account_id(),
+            "nft_mint",
+            &json!({
+                "token_series_id": "1",
+                "receiver_id": root.account_id(),
+            }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            STORAGE_MINT_ESTIMATE
+        ).assert_success();
+    }
+
+    let outcome = root.call(
+        nft.account_id(),
+        "nft_tokens_by_series",
+        &json!({
+            "token_series_id": "1",
+            "from_index": (total_tokens - 50).to_string(),
+            "limit": 50u64,
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0
+    );
+    let offset_gas = outcome.gas_burnt();
+    println!("[TOKENS BY SERIES OFFSET] Gas burnt price: {} TeraGas", offset_gas as f64 / 1e12);
+
+    let outcome = root.call(
+        nft.account_id(),
+        "nft_tokens_by_series_cursor",
+        &json!({
+            "token_series_id": "1",
+            "cursor": format!("1:{}", total_tokens - 50),
+            "limit": 50u64,
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0
+    );
+    outcome.assert_success();
+    let cursor_gas = outcome.gas_burnt();
+    println!("[TOKENS BY SERIES CURSOR] Gas burnt price: {} TeraGas", cursor_gas as f64 / 1e12);
+    assert!(cursor_gas < offset_gas);
+
+    let outcome = root.call(
+        nft.account_id(),
+        "nft_tokens_cursor",
+        &json!({
+            "cursor": format!("1:{}", total_tokens - 50),
+            "limit": 50u64,
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0
+    );
+    outcome.assert_success();
+    println!("[TOKENS CURSOR] Gas burnt price: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
+
+    let outcome = root.call(
+        nft.account_id(),
+        "nft_tokens_for_owner_cursor",
+        &json!({
+            "account_id": root.account_id(),
+            "cursor": format!("1:{}", total_tokens - 50),
+            "limit": 50u64,
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0
+    );
+    outcome.assert_success();
+    println!("[TOKENS FOR OWNER CURSOR] Gas burnt price: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
+}
+
+/// Storage added by the second mint of a new series, so only the per-token records are counted
+fn measure_mint_storage_usage(root: &UserAccount, nft: &UserAccount) -> u64 {
+    root.call(
+        nft.account_id(),
+        "nft_create_series",
+        &json!({
+            "token_metadata": {
+                "title": "A".repeat(200),
+                "reference": "A".repeat(59),
+                "media": "A".repeat(59),
+                "copies": 100u64,
+            },
+            "royalty": {
+                "0".repeat(64): 1000u32
+            },
+            "creator_id": root.account_id()
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("1")
+    ).assert_success();
+
+    // first mint of the series also creates its owner index
+    root.call(
+        nft.account_id(),
+        "nft_mint",
+        &json!({
+            "token_series_id": "1",
+            "receiver_id": root.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        STORAGE_MINT_ESTIMATE
+    ).assert_success();
+
+    let initial_storage_usage = nft.account().unwrap().storage_usage;
+    root.call(
+        nft.account_id(),
+        "nft_mint",
+        &json!({
+            "token_series_id": "1",
+            "receiver_id": "a".repeat(64),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        STORAGE_MINT_ESTIMATE
+    ).assert_success();
+    nft.account().unwrap().storage_usage - initial_storage_usage
+}
+
+#[test]
+fn simulate_mint_compact_token_data() {
+    let (root, nft, treasury) = init();
+
+    // same contract built to store the full TokenMetadata per token
+    let legacy = root.deploy(&LEGACY_NFT_WASM_BYTES.to_vec(), "legacy".to_string(), to_yocto("100"));
+    root.call(
+        legacy.account_id(),
+        "new_default_meta",
+        &json!({
+            "owner_id": root.account_id(),
+            "treasury_id": treasury.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0
+    ).assert_success();
+
+    let compact_storage_usage = measure_mint_storage_usage(&root, &nft.user_account);
+    let full_storage_usage = measure_mint_storage_usage(&root, &legacy);
+
+    println!("[MINT COMPACT] Storage price: {} yoctoNEAR", compact_storage_usage as u128 * 10u128.pow(19));
+    println!("[MINT FULL METADATA] Storage price: {} yoctoNEAR", full_storage_usage as u128 * 10u128.pow(19));
+    assert!(compact_storage_usage < full_storage_usage);
+    assert!((compact_storage_usage as u128 * 10u128.pow(19)) <= STORAGE_MINT_ESTIMATE);
+}
+
+#[test]
+fn simulate_fractionalize_buyout() {
+    let (root, nft, treasury) = init();
+
+    let alice = root.create_user("alice".to_string(), to_yocto("100"));
+    let bob = root.create_user("bob".to_string(), to_yocto("100"));
+
+    root.call(
+        nft.account_id(),
+        "set_fraction_share_code",
+        &SHARE_WASM_BYTES.to_vec(),
+        DEFAULT_GAS,
+        to_yocto("5")
+    ).assert_success();
+
+    root.call(
+        nft.account_id(),
+        "nft_create_series",
+        &json!({
+            "token_metadata": {
+                "title": "A".repeat(200),
+                "reference": "A".repeat(59),
+                "media": "A".repeat(59),
+                "copies": 1u64,
+            },
+            "royalty": {
+                treasury.account_id(): 1000u32
+            },
+            "creator_id": root.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("1")
+    ).assert_success();
+
+    root.call(
+        nft.account_id(),
+        "nft_mint",
+        &json!({
+            "token_series_id": "1",
+            "receiver_id": alice.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        STORAGE_MINT_ESTIMATE
+    ).assert_success();
+
+    let outcome = alice.call(
+        nft.account_id(),
+        "nft_fractionalize",
+        &json!({
+            "token_id": "1:1",
+            "total_shares": "1000",
+            "reserve_price": to_yocto("10").to_string(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("5")
+    );
+    outcome.assert_success();
+    println!("[FRACTIONALIZE] Gas burnt price: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
+
+    // the shares are a NEP-141 token on a sub-account of the nft contract
+    let share_id = format!("v1.{}", NFT_CONTRACT_ID);
+    let vault: near_sdk::serde_json::Value = root.view(
+        nft.account_id(),
+        "nft_get_vault",
+        &json!({"token_id": "1:1"}).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(vault["share_id"], share_id);
+    assert_eq!(vault["deployed"], true);
+
+    let metadata: near_sdk::serde_json::Value = root.view(
+        share_id.clone(),
+        "ft_metadata",
+        &json!({}).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(metadata["name"], "1:1 shares");
+
+    bob.call(
+        share_id.clone(),
+        "storage_deposit",
+        &json!({}).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("0.01")
+    ).assert_success();
+
+    alice.call(
+        share_id.clone(),
+        "ft_transfer",
+        &json!({
+            "receiver_id": bob.account_id(),
+            "amount": "400",
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    ).assert_success();
+
+    let balance: String = root.view(
+        share_id.clone(),
+        "ft_balance_of",
+        &json!({"account_id": bob.account_id()}).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(balance, "400");
+
+    let treasury_balance = treasury.account().unwrap().amount;
+
+    bob.call(
+        nft.account_id(),
+        "nft_buyout",
+        &json!({
+            "token_id": "1:1",
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("10")
+    ).assert_success();
+
+    let token: near_sdk::serde_json::Value = root.view(
+        nft.account_id(),
+        "nft_token",
+        &json!({"token_id": "1:1"}).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(token["owner_id"], bob.account_id());
+    assert_eq!(treasury.account().unwrap().amount - treasury_balance, to_yocto("1"));
+
+    let alice_balance = alice.account().unwrap().amount;
+    let outcome = alice.call(
+        share_id.clone(),
+        "ft_transfer_call",
+        &json!({
+            "receiver_id": nft.account_id(),
+            "amount": "600",
+            "msg": "claim",
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    );
+    outcome.assert_success();
+    println!("[FRACTION CLAIM] Gas burnt price: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
+    assert!(alice.account().unwrap().amount > alice_balance + to_yocto("5.3"));
+
+    let balance: String = root.view(
+        share_id.clone(),
+        "ft_balance_of",
+        &json!({"account_id": alice.account_id()}).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(balance, "0");
+
+    // the new owner can fractionalize again while bob's shares are still unclaimed
+    bob.call(
+        nft.account_id(),
+        "nft_fractionalize",
+        &json!({
+            "token_id": "1:1",
+            "total_shares": "10",
+            "reserve_price": to_yocto("20").to_string(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("5")
+    ).assert_success();
+
+    let vault: near_sdk::serde_json::Value = root.view(
+        nft.account_id(),
+        "nft_get_fraction_vault",
+        &json!({"share_id": share_id}).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(vault["outstanding_shares"], "400");
+}
+
+#[test]
+fn simulate_token_gated_check() {
+    let (root, nft, treasury) = init();
+
+    let alice = root.create_user("alice".to_string(), to_yocto("100"));
+
+    // a gated contract asks the nft contract through a promise and reads the bool in its callback
+    let gate = root.deploy(&GATE_WASM_BYTES.to_vec(), "gate".to_string(), to_yocto("10"));
+    root.call(
+        gate.account_id(),
+        "new",
+        &json!({
+            "nft_id": nft.account_id(),
+            "token_series_id": "1",
+            "min_balance": "2",
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0
+    ).assert_success();
+
+    root.call(
+        nft.account_id(),
+        "nft_create_series",
+        &json!({
+            "token_metadata": {
+                "title": "A".repeat(200),
+                "reference": "A".repeat(59),
+                "media": "A".repeat(59),
+                "copies": 10u64,
+            },
+            "royalty": {
+                treasury.account_id(): 1000u32
+            },
+            "creator_id": root.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        to_yocto("1")
+    ).assert_success();
+
+    let enter = || -> bool {
+        let outcome = alice.call(
+            gate.account_id(),
+            "enter",
+            &json!({}).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0
+        );
+        outcome.assert_success();
+        println!("[TOKEN GATE] Gas burnt price: {} TeraGas", outcome.gas_burnt() as f64 / 1e12);
+        let entered: bool = outcome.unwrap_json();
+        let is_member: bool = root.view(
+            gate.account_id(),
+            "is_member",
+            &json!({"account_id": alice.account_id()}).to_string().into_bytes(),
+        ).unwrap_json();
+        assert_eq!(entered, is_member);
+        entered
+    };
+    assert!(!enter());
+
+    for _ in 0..2 {
+        root.call(
+            nft.account_id(),
+            "nft_mint",
+            &json!({
+                "token_series_id": "1",
+                "receiver_id": alice.account_id(),
+            }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            STORAGE_MINT_ESTIMATE
+        ).assert_success();
+    }
+
+    let balance: String = root.view(
+        nft.account_id(),
+        "nft_balance_of_series",
+        &json!({
+            "account_id": alice.account_id(),
+            "token_series_id": "1",
+        }).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(balance, "2");
+    assert!(enter());
+
+    alice.call(
+        nft.account_id(),
+        "nft_transfer",
+        &json!({
+            "token_id": "1:1",
+            "receiver_id": root.account_id(),
+        }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        1
+    ).assert_success();
+    assert!(!enter());
+}
@@ -0,0 +1,90 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedSet;
+use near_sdk::json_types::{ValidAccountId, U64};
+use near_sdk::serde_json;
+use near_sdk::{
+    env, ext_contract, near_bindgen, AccountId, BorshStorageKey, Gas, PanicOnDefault, Promise, PromiseResult,
+};
+
+near_sdk::setup_alloc!();
+
+const GAS_FOR_HOLDS_AT_LEAST: Gas = 10_000_000_000_000;
+const GAS_FOR_ON_HOLDS_CHECKED: Gas = 10_000_000_000_000;
+const NO_DEPOSIT: u128 = 0;
+
+#[ext_contract(ext_nft)]
+trait TokenGate {
+    fn nft_holds_at_least(&self, account_id: AccountId, token_series_id: String, min_balance: U64) -> bool;
+}
+
+#[ext_contract(ext_self)]
+trait GateResolver {
+    fn on_holds_checked(&mut self, account_id: AccountId) -> bool;
+}
+
+/// Minimal token-gated contract: callers become members if they hold at least `min_balance`
+/// tokens of `token_series_id` on the nft contract. Used by the simulation tests.
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+pub struct Contract {
+    nft_id: AccountId,
+    token_series_id: String,
+    min_balance: u64,
+    members: UnorderedSet<AccountId>,
+}
+
+#[derive(BorshSerialize, BorshStorageKey)]
+enum StorageKey {
+    Members,
+}
+
+#[near_bindgen]
+impl Contract {
+    #[init]
+    pub fn new(nft_id: ValidAccountId, token_series_id: String, min_balance: U64) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
+        Self {
+            nft_id: nft_id.into(),
+            token_series_id,
+            min_balance: min_balance.into(),
+            members: UnorderedSet::new(StorageKey::Members),
+        }
+    }
+
+    /// Asks the nft contract about the caller and resolves to whether they got in
+    pub fn enter(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+        ext_nft::nft_holds_at_least(
+            account_id.clone(),
+            self.token_series_id.clone(),
+            U64::from(self.min_balance),
+            &self.nft_id,
+            NO_DEPOSIT,
+            GAS_FOR_HOLDS_AT_LEAST,
+        )
+        .then(ext_self::on_holds_checked(
+            account_id,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ON_HOLDS_CHECKED,
+        ))
+    }
+
+    #[private]
+    pub fn on_holds_checked(&mut self, account_id: AccountId) -> bool {
+        let holds = match env::promise_result(0) {
+            PromiseResult::Successful(value) => serde_json::from_slice::<bool>(&value).unwrap_or(false),
+            _ => false,
+        };
+        if holds {
+            self.members.insert(&account_id);
+        } else {
+            self.members.remove(&account_id);
+        }
+        holds
+    }
+
+    pub fn is_member(&self, account_id: ValidAccountId) -> bool {
+        self.members.contains(account_id.as_ref())
+    }
+}